@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+use cortex_m_rt::entry;
+use panic_halt as _;
+use rtt_target::rtt_init;
+
+#[entry]
+fn main() -> ! {
+    let channels = rtt_init! {
+        up: {
+            0: { size: 256, name: "Task 0" }
+            1: { size: 64, name: "Task 1" }
+            2: { size: 64, name: "Task 2" }
+            3: { size: 64, name: "Task 3" }
+            4: { size: 64, name: "Task 4" }
+            5: { size: 64, name: "Task 5" }
+            6: { size: 64, name: "Task 6" }
+            7: { size: 64, name: "Task 7" }
+        }
+        down: {
+            0: { size: 16, name: "Input 0" }
+            1: { size: 16, name: "Input 1" }
+            2: { size: 16, name: "Input 2" }
+            3: { size: 16, name: "Input 3" }
+        }
+    };
+
+    let mut output = channels.up.7;
+    let mut input = channels.down.3;
+    let mut buf = [0u8; 16];
+
+    writeln!(output, "Hello from the last up channel!").ok();
+
+    loop {
+        let count = input.read(&mut buf[..]);
+        if count > 0 {
+            output.write(&buf[..count]);
+        }
+    }
+}
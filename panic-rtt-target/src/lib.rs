@@ -6,6 +6,8 @@
 //! Panics are always logged to the print and defmt channels, if they are configured. Upon panicking
 //! the channel mode is also automatically set to `BlockIfFull`, so that the full message will
 //! always be logged.
+//! If a dedicated panic channel has been set with `set_panic_channel` or `rtt_init_print_panic`,
+//! the message is also written to it and flushed before halting.
 //! If the code somehow manages to panic at runtime before RTT is initialized (quite unlikely),
 //! or if the print channel doesn't exist, nothing is logged.
 //!
@@ -43,7 +45,7 @@
 use core::{fmt::Write, panic::PanicInfo};
 use portable_atomic::{compiler_fence, Ordering};
 
-use rtt_target::{with_terminal_channel, ChannelMode};
+use rtt_target::{with_panic_channel, with_terminal_channel, ChannelMode};
 
 #[inline(never)]
 #[panic_handler]
@@ -59,6 +61,10 @@ fn panic(info: &PanicInfo) -> ! {
             writeln!(channel, "{}", info).ok();
         });
 
+        with_panic_channel(|channel| {
+            writeln!(channel, "{}", info).ok();
+        });
+
         // we should never leave critical section
         loop {
             compiler_fence(Ordering::SeqCst);
@@ -0,0 +1,105 @@
+use core::cell::UnsafeCell;
+use portable_atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::UpChannel;
+
+/// An up channel that can be shared between contexts, such as `main` and interrupt handlers,
+/// without a critical section.
+///
+/// Writing through a shared reference is made safe with a lock that is only ever tried, never
+/// waited for: if a write interrupts another write to the same channel, for example an interrupt
+/// handler that preempts `main` in the middle of a message, the interrupting write is dropped
+/// instead of deadlocking. [`write`](AtomicUpChannel::write) then returns `None`, so that the lost
+/// write can be told apart from one that was muted or skipped, and the collision is counted in
+/// [`collisions`](AtomicUpChannel::collisions). Writes dropped this way are not included in the
+/// `stats` counters, as those can only be updated while holding the lock.
+///
+/// This makes every write a little more expensive, and writes from contexts that collide are lost
+/// even if the buffer has room. A separate channel for each context avoids both and is still the
+/// better choice when buffer memory allows it. Use this wrapper when a single channel has to be
+/// shared, instead of sharing an [`UpChannel`] through a raw pointer.
+///
+/// Only available on targets that support atomic compare-and-swap.
+///
+/// ```
+/// static LOG: StaticCell<AtomicUpChannel> = StaticCell::new();
+///
+/// let log: &'static AtomicUpChannel = LOG.init(AtomicUpChannel::new(channels.up.1));
+/// // Pass `log` to interrupt handlers and use it from main
+/// if log.write(b"Hello from any context\n").is_none() {
+///     // Another context was writing, the message is lost
+/// }
+/// ```
+pub struct AtomicUpChannel {
+    channel: UnsafeCell<UpChannel>,
+    busy: AtomicBool,
+    collisions: AtomicUsize,
+}
+
+// Safety: The channel, including its statistics, is only accessed while holding the lock.
+unsafe impl Sync for AtomicUpChannel {}
+
+impl AtomicUpChannel {
+    /// Wraps a channel for sharing between contexts.
+    pub fn new(channel: UpChannel) -> Self {
+        AtomicUpChannel {
+            channel: UnsafeCell::new(channel),
+            busy: AtomicBool::new(false),
+            collisions: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the wrapped channel.
+    pub fn into_inner(self) -> UpChannel {
+        self.channel.into_inner()
+    }
+
+    /// Writes `buf` to the channel and returns the number of bytes written. Behavior when the
+    /// buffer is full is subject to the channel blocking mode. If another write to this channel is
+    /// in progress in a context that was interrupted, nothing is written and `None` is returned.
+    pub fn write(&self, buf: &[u8]) -> Option<usize> {
+        self.try_with(|channel| channel.write(buf))
+    }
+
+    /// Returns the number of operations that were dropped because another context was using the
+    /// channel at the same time, saturating at `usize::MAX`.
+    pub fn collisions(&self) -> usize {
+        self.collisions.load(Ordering::Relaxed)
+    }
+
+    /// Calls `f` with exclusive access to the channel and returns its result, or returns `None`
+    /// without calling `f` if the channel is in use by an interrupted context. Such a collision is
+    /// counted in [`collisions`](AtomicUpChannel::collisions).
+    ///
+    /// This can be used for any other channel operation, such as formatted writing:
+    ///
+    /// ```
+    /// log.try_with(|channel| writeln!(channel, "value: {}", value).ok());
+    /// ```
+    pub fn try_with<R, F: FnOnce(&mut UpChannel) -> R>(&self, f: F) -> Option<R> {
+        if self
+            .busy
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            self.collisions
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_add(1))
+                .ok();
+            return None;
+        }
+
+        // Released in drop so that the lock can't stay taken if `f` unwinds
+        struct Unlock<'a>(&'a AtomicBool);
+
+        impl Drop for Unlock<'_> {
+            fn drop(&mut self) {
+                self.0.store(false, Ordering::Release);
+            }
+        }
+
+        let _unlock = Unlock(&self.busy);
+
+        // Safety: The lock guarantees exclusive access
+        Some(f(unsafe { &mut *self.channel.get() }))
+    }
+}
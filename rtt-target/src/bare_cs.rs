@@ -0,0 +1,15 @@
+//! No-op critical section implementation for the `bare-unsafe-cs` feature.
+
+struct BareCriticalSection;
+
+critical_section::set_impl!(BareCriticalSection);
+
+// SAFETY: This is not actually safe. The feature documentation requires that the program only ever
+// uses RTT from a single execution context, in which case no mutual exclusion is needed.
+unsafe impl critical_section::Impl for BareCriticalSection {
+    unsafe fn acquire() -> critical_section::RawRestoreState {
+        Default::default()
+    }
+
+    unsafe fn release(_restore_state: critical_section::RawRestoreState) {}
+}
@@ -0,0 +1,136 @@
+use crate::UpChannel;
+use core::cmp::min;
+
+/// Largest number of data bytes in a single COBS block.
+const MAX_BLOCK: usize = 254;
+
+/// Passes the COBS encoding of `buf`, including the zero delimiter, to `f` in pieces.
+fn encode<F: FnMut(&[u8])>(buf: &[u8], mut f: F) {
+    let mut rest = buf;
+
+    loop {
+        let run = rest
+            .iter()
+            .take(MAX_BLOCK)
+            .position(|&b| b == 0)
+            .unwrap_or_else(|| min(rest.len(), MAX_BLOCK));
+
+        f(&[run as u8 + 1]);
+        f(&rest[..run]);
+
+        if run == rest.len() {
+            break;
+        }
+
+        // Either a zero ends the block and is replaced by the code byte, or the block is full
+        // and the data continues in the next one. A full block has no implied zero, so a zero
+        // right after it starts the next block instead of ending this one.
+        rest = if run < MAX_BLOCK && rest[run] == 0 {
+            &rest[run + 1..]
+        } else {
+            &rest[run..]
+        };
+    }
+
+    f(&[0]);
+}
+
+impl UpChannel {
+    /// Encodes `buf` with COBS (Consistent Overhead Byte Stuffing), appends a zero delimiter and
+    /// writes the result to the channel as a single frame. Returns the number of encoded bytes
+    /// written, which is zero if the frame was dropped.
+    ///
+    /// The frame is either written in full or not at all, so a host side decoder never sees a
+    /// partial frame. In `NoBlockTrim` mode frames that don't fit are skipped. In `BlockIfFull`
+    /// mode the write blocks until the whole frame has been written, except that with a
+    /// [block timeout](UpChannel::set_block_timeout) the frame is skipped if there is no room for
+    /// all of it by the time the timeout expires.
+    ///
+    /// The encoding is done on the fly, so no extra memory is needed, but the encoded frame is up
+    /// to `buf.len() + buf.len() / 254 + 2` bytes long. In the non-blocking modes, and in
+    /// `BlockIfFull` mode with a block timeout, the channel buffer must be at least one byte larger
+    /// than that for the frame to ever fit.
+    pub fn write_cobs_frame(&mut self, buf: &[u8]) -> usize {
+        let mut len = 0;
+        encode(buf, |part| len += part.len());
+
+        let mode = self.frame_mode(len);
+        let mut writer = self.binary_writer();
+        encode(buf, |part| writer.write_with_mode(mode, part));
+
+        if writer.is_skipped() {
+            return 0;
+        }
+
+        writer.commit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rtt::tests::{channel, read_all};
+    use crate::{ChannelMode, UpChannel};
+    use std::vec::Vec;
+
+    fn decode(frame: &[u8]) -> Vec<u8> {
+        let (delimiter, mut rest) = frame.split_last().unwrap();
+        assert_eq!(*delimiter, 0);
+
+        let mut out = Vec::new();
+        while let Some((&code, tail)) = rest.split_first() {
+            let len = code as usize - 1;
+            out.extend_from_slice(&tail[..len]);
+            rest = &tail[len..];
+
+            if code != 0xff && !rest.is_empty() {
+                out.push(0);
+            }
+        }
+
+        out
+    }
+
+    fn roundtrip(data: &[u8]) {
+        let chan = channel(1024, ChannelMode::NoBlockSkip);
+        let mut up = unsafe { UpChannel::new(chan, 0) };
+
+        let written = up.write_cobs_frame(data);
+        let frame = read_all(chan);
+
+        assert_eq!(written, frame.len());
+        assert!(!frame[..frame.len() - 1].contains(&0));
+        assert_eq!(decode(&frame), data);
+    }
+
+    fn non_zero(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 255) as u8 + 1).collect()
+    }
+
+    #[test]
+    fn roundtrip_block_boundaries() {
+        for len in [0, 1, 253, 254, 255, 256, 600] {
+            roundtrip(&non_zero(len));
+        }
+    }
+
+    #[test]
+    fn roundtrip_zero_at_block_boundaries() {
+        for len in [254, 255, 256] {
+            for zero_at in [252, 253, 254, 255] {
+                if zero_at < len {
+                    let mut data = non_zero(len);
+                    data[zero_at] = 0;
+                    roundtrip(&data);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn roundtrip_zeros() {
+        roundtrip(&[0]);
+        roundtrip(&[0, 0]);
+        roundtrip(&[1, 0]);
+        roundtrip(&[0, 1]);
+    }
+}
@@ -0,0 +1,40 @@
+use crate::{UpChannel, WouldBlockError};
+
+/// Computes the CRC-16/CCITT-FALSE checksum of `data` (polynomial 0x1021, initial value 0xffff, no
+/// reflection and no final XOR).
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xffffu16;
+
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+impl UpChannel {
+    /// Writes `buf` to the channel as a frame like [`write_frame`](UpChannel::write_frame), followed
+    /// by a little-endian CRC-16/CCITT-FALSE checksum of the payload. Returns the number of bytes
+    /// written including the header and the checksum, or an error if the frame was dropped.
+    ///
+    /// The header, payload and checksum are written as one unit, so the frame is either written in
+    /// full or not at all. If the checksum doesn't match, the host can discard the frame and resync
+    /// by searching for the next header with a valid checksum.
+    ///
+    /// The checksum is computed bitwise without a lookup table to keep the code small.
+    pub fn write_frame_crc(&mut self, buf: &[u8]) -> Result<usize, WouldBlockError> {
+        self.write_frame_parts(&[
+            &(buf.len() as u32).to_le_bytes(),
+            buf,
+            &crc16(buf).to_le_bytes(),
+        ])
+    }
+}
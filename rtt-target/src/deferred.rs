@@ -0,0 +1,166 @@
+use crate::{UpChannel, WouldBlockError};
+
+/// The maximum number of bytes of encoded arguments in a [`DeferredRecord`].
+pub const DEFERRED_MAX_ARGS_SIZE: usize = 64;
+
+/// Computes the ID that [`rtt_defer`] sends for a format string: the 32-bit FNV-1a hash of the
+/// UTF-8 bytes of the string, with the upper and lower halves XORed together into a `u16`.
+///
+/// The host side tool has to compute the same hash over the format strings in the source code to
+/// map the IDs back to strings. Two different strings can have the same ID, in which case the host
+/// can't tell which one was used.
+///
+/// [`rtt_defer`]: crate::rtt_defer
+pub const fn deferred_id(format: &str) -> u16 {
+    let bytes = format.as_bytes();
+    let mut hash = 0x811c_9dc5u32;
+
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+        i += 1;
+    }
+
+    ((hash >> 16) ^ (hash & 0xffff)) as u16
+}
+
+/// A value that can be sent as an argument of a [`DeferredRecord`].
+///
+/// Each argument is encoded as a one byte type tag followed by the value in little-endian byte
+/// order. The tags are:
+///
+/// | Type   | Tag | Type   | Tag |
+/// |--------|-----|--------|-----|
+/// | `u8`   | 0   | `i8`   | 4   |
+/// | `u16`  | 1   | `i16`  | 5   |
+/// | `u32`  | 2   | `i32`  | 6   |
+/// | `u64`  | 3   | `i64`  | 7   |
+/// | `f32`  | 8   | `f64`  | 9   |
+/// | `bool` | 10  |        |     |
+///
+/// `usize` and `isize` are sent as `u32`/`i32` or `u64`/`i64` depending on the target. A `bool` is
+/// sent as a single byte that is 0 or 1.
+pub trait DeferredArg {
+    /// Writes the tag and value to the start of `buf` and returns the number of bytes written.
+    fn encode(&self, buf: &mut [u8; 9]) -> usize;
+}
+
+macro_rules! impl_deferred_arg {
+    ($($ty:ty => $tag:literal),* $(,)?) => {
+        $(
+            impl DeferredArg for $ty {
+                fn encode(&self, buf: &mut [u8; 9]) -> usize {
+                    let bytes = self.to_le_bytes();
+                    buf[0] = $tag;
+                    buf[1..=bytes.len()].copy_from_slice(&bytes);
+                    1 + bytes.len()
+                }
+            }
+        )*
+    };
+}
+
+impl_deferred_arg! {
+    u8 => 0, u16 => 1, u32 => 2, u64 => 3,
+    i8 => 4, i16 => 5, i32 => 6, i64 => 7,
+    f32 => 8, f64 => 9,
+}
+
+#[cfg(target_pointer_width = "32")]
+impl_deferred_arg! { usize => 2, isize => 6 }
+
+#[cfg(target_pointer_width = "64")]
+impl_deferred_arg! { usize => 3, isize => 7 }
+
+impl DeferredArg for bool {
+    fn encode(&self, buf: &mut [u8; 9]) -> usize {
+        buf[0] = 10;
+        buf[1] = *self as u8;
+        2
+    }
+}
+
+impl<T: DeferredArg + ?Sized> DeferredArg for &T {
+    fn encode(&self, buf: &mut [u8; 9]) -> usize {
+        (**self).encode(buf)
+    }
+}
+
+/// A log message whose formatting is left to the host, built by [`rtt_defer`].
+///
+/// Instead of the formatted text, the record contains an ID of the format string and the raw
+/// values of the arguments, which saves both code size and CPU time on the target. On the wire a
+/// record is:
+///
+/// - the format string ID as a little-endian `u16`, see [`deferred_id`]
+/// - the length of the encoded arguments in bytes as a `u8`
+/// - the encoded arguments, see [`DeferredArg`]
+///
+/// [`rtt_defer`]: crate::rtt_defer
+pub struct DeferredRecord {
+    buf: [u8; 3 + DEFERRED_MAX_ARGS_SIZE],
+    len: usize,
+}
+
+impl DeferredRecord {
+    /// Creates a record without arguments for the format string with the given ID.
+    pub fn new(id: u16) -> Self {
+        let mut buf = [0; 3 + DEFERRED_MAX_ARGS_SIZE];
+        buf[..2].copy_from_slice(&id.to_le_bytes());
+
+        DeferredRecord { buf, len: 3 }
+    }
+
+    /// Appends an argument to the record. Arguments that don't fit in the
+    /// [`DEFERRED_MAX_ARGS_SIZE`] bytes reserved for them are left out.
+    pub fn push<T: DeferredArg>(&mut self, arg: T) {
+        let mut encoded = [0; 9];
+        let n = arg.encode(&mut encoded);
+
+        if self.len + n > self.buf.len() {
+            return;
+        }
+
+        self.buf[self.len..self.len + n].copy_from_slice(&encoded[..n]);
+        self.len += n;
+        self.buf[2] = (self.len - 3) as u8;
+    }
+
+    /// Returns the encoded record.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+impl UpChannel {
+    /// Writes a [`DeferredRecord`] to the channel and returns the number of bytes written, or an
+    /// error if the record was dropped. A record is always written in full or not at all, so the
+    /// host side decoder doesn't get out of sync. The channel mode and block timeout apply as for
+    /// [`write_frame`](UpChannel::write_frame).
+    pub fn write_deferred(&mut self, record: &DeferredRecord) -> Result<usize, WouldBlockError> {
+        self.write_frame_parts(&[record.as_bytes()])
+    }
+}
+
+/// Writes a log message to an up channel as a [`DeferredRecord`], leaving the formatting to the
+/// host. This requires the `deferred` feature.
+///
+/// The first argument is the channel, the second one the format string and the rest are the values
+/// to format, which must implement [`DeferredArg`]. The format string itself is not sent or checked
+/// on the target; only its ID is. Evaluates to the result of [`UpChannel::write_deferred`].
+///
+/// ```
+/// rtt_defer!(channels.up.1, "temperature: {} humidity: {}", temp, humidity).ok();
+/// ```
+///
+/// [`UpChannel::write_deferred`]: crate::UpChannel::write_deferred
+#[macro_export]
+macro_rules! rtt_defer {
+    ($channel:expr, $format:literal $(, $arg:expr)* $(,)?) => {{
+        const ID: u16 = $crate::deferred_id($format);
+        let mut record = $crate::DeferredRecord::new(ID);
+        $( record.push($arg); )*
+        $channel.write_deferred(&record)
+    }};
+}
@@ -1,4 +1,4 @@
-use crate::UpChannel;
+use crate::{UpChannel, WouldBlockError};
 use portable_atomic::{AtomicBool, Ordering};
 
 static mut CHANNEL: Option<UpChannel> = None;
@@ -7,10 +7,29 @@ static mut CHANNEL: Option<UpChannel> = None;
 struct Logger;
 
 /// Sets the channel to use for [`defmt`] macros.
+///
+/// This crate provides the `#[defmt::global_logger]` when the `defmt` feature is enabled, so no
+/// other logger must be defined. The logger holds a critical section for the duration of each
+/// defmt frame, the same way the print macros do. Messages logged before the channel is set are
+/// discarded.
 pub fn set_defmt_channel(channel: UpChannel) {
     unsafe { CHANNEL = Some(channel) }
 }
 
+/// Writes an already encoded defmt frame to `channel` and returns the number of bytes written, or
+/// an error if the frame was dropped.
+///
+/// This is a lower level alternative to [`set_defmt_channel`] for setups that encode frames
+/// themselves, for example to send defmt output to a different channel than the global logger. The
+/// frame is always written in full or not at all regardless of the channel mode, because a partial
+/// frame would corrupt the decoding of the frames that follow it on the host. In `BlockIfFull`
+/// mode this waits until the whole frame has been written, or until the block timeout of the
+/// channel expires, in which case the frame is dropped. See [`UpChannel::write_frame`] for
+/// details.
+pub fn write_defmt_frame(channel: &mut UpChannel, frame: &[u8]) -> Result<usize, WouldBlockError> {
+    channel.write_frame_parts(&[frame])
+}
+
 /// Global logger lock.
 static TAKEN: AtomicBool = AtomicBool::new(false);
 static mut CS_RESTORE: critical_section::RestoreState = critical_section::RestoreState::invalid();
@@ -66,7 +85,9 @@ fn do_write(bytes: &[u8]) {
     unsafe {
         let channel = core::ptr::addr_of_mut!(CHANNEL);
         if let Some(Some(c)) = channel.as_mut() {
-            c.write(bytes);
+            let mut writer = c.binary_writer();
+            writer.write(bytes);
+            writer.commit();
         }
     }
 }
@@ -0,0 +1,87 @@
+use crate::{DownChannel, UpChannel};
+use core::convert::Infallible;
+
+impl embedded_io::ErrorType for UpChannel {
+    type Error = Infallible;
+}
+
+impl embedded_io::Write for UpChannel {
+    /// Writes `buf` to the channel.
+    ///
+    /// Like the [`core::fmt::Write`] implementation, this only blocks if the channel is in
+    /// `BlockIfFull` mode. If only part of `buf` could be written, for example in `NoBlockTrim`
+    /// mode, the returned count is that part, so that callers such as `write_all` try again with
+    /// the rest.
+    ///
+    /// If nothing could be written, the data is discarded but still reported as written, because
+    /// `embedded_io` does not allow a write to return zero bytes for a non-empty `buf`. This is the
+    /// case for data that doesn't fit in `NoBlockSkip` mode or in a full buffer, for all data while
+    /// the channel is [muted](UpChannel::set_muted) or has no buffer, and in `BlockIfFull` mode
+    /// when a [block timeout](UpChannel::set_block_timeout) expires before anything was written.
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let written = UpChannel::write(self, buf);
+
+        Ok(if written > 0 { written } else { buf.len() })
+    }
+
+    /// Waits until the host has read all data in `BlockIfFull` mode, and does nothing in the
+    /// non-blocking modes. See [`UpChannel::drain`].
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.drain();
+        Ok(())
+    }
+}
+
+impl embedded_io::ErrorType for DownChannel {
+    type Error = Infallible;
+}
+
+impl embedded_io::Read for DownChannel {
+    /// Reads data from the channel, spinning until at least one byte is available as required by
+    /// the `embedded_io` contract. Use [`DownChannel::read`] directly for a non-blocking read.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let count = DownChannel::read(self, buf);
+            if count > 0 {
+                return Ok(count);
+            }
+            crate::rtt::block_wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rtt::tests::{channel, read_all};
+    use crate::{ChannelMode, UpChannel};
+    use embedded_io::Write;
+
+    #[test]
+    fn discarded_writes_are_not_reported_as_zero() {
+        let mut up = unsafe { UpChannel::new(channel(16, ChannelMode::BlockIfFull), 0) };
+
+        up.set_muted(true);
+        assert_eq!(Write::write(&mut up, b"muted"), Ok(5));
+        assert_eq!(Write::write_all(&mut up, b"muted"), Ok(()));
+
+        up.set_muted(false);
+        up.set_block_timeout(Some(10));
+        assert_eq!(Write::write(&mut up, &[0; 15]), Ok(15));
+        assert_eq!(Write::write(&mut up, b"full"), Ok(4));
+    }
+
+    #[test]
+    fn trimmed_writes_report_the_part_that_was_written() {
+        let chan = channel(16, ChannelMode::NoBlockTrim);
+        let mut up = unsafe { UpChannel::new(chan, 0) };
+
+        assert_eq!(Write::write(&mut up, &[1; 10]), Ok(10));
+        assert_eq!(Write::write(&mut up, &[2; 10]), Ok(5));
+        assert_eq!(Write::write(&mut up, &[3; 10]), Ok(10));
+        assert_eq!(read_all(chan), [[1; 10].as_slice(), &[2; 5]].concat());
+    }
+}
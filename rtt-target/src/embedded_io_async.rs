@@ -0,0 +1,150 @@
+//! There is no interrupt or other event that signals when the host has read from or written to a
+//! buffer, so waiting is implemented by waking the task immediately and yielding to the executor.
+//! The buffer is then polled again the next time the executor gets around to the task.
+
+use crate::{ChannelMode, DownChannel, UpChannel};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+impl embedded_io_async::Write for UpChannel {
+    /// Writes `buf` to the channel.
+    ///
+    /// In `BlockIfFull` mode this yields to the executor while the buffer is full instead of
+    /// spinning, and gives up after the [block timeout](UpChannel::set_block_timeout) if one is
+    /// set. The data is then discarded but reported as written, as in the other cases where the
+    /// [`embedded_io::Write`] implementation discards data, such as a full buffer in the
+    /// non-blocking modes or a muted channel. A partial write in `NoBlockTrim` mode reports the
+    /// part that was written.
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        // Waiting for space makes no sense if nothing would be written anyway
+        if self.mode() != ChannelMode::BlockIfFull
+            || buf.is_empty()
+            || self.is_muted()
+            || self.buffer_len() == 0
+        {
+            return embedded_io::Write::write(self, buf);
+        }
+
+        let mut polls: u32 = 0;
+        while self.writable_data_len() == 0 {
+            if let Some(max_polls) = self.block_timeout() {
+                if polls >= max_polls {
+                    break;
+                }
+                polls += 1;
+            }
+
+            yield_now().await;
+        }
+
+        let mut writer = self.writer();
+        writer.write_with_mode(ChannelMode::NoBlockTrim, buf);
+        let written = writer.commit();
+
+        // Nothing is written if the wait timed out, which embedded_io doesn't allow to report
+        Ok(if written > 0 { written } else { buf.len() })
+    }
+
+    /// Waits until the host has read everything in the buffer if the channel is in `BlockIfFull`
+    /// mode, yielding to the executor between polls. Like [`UpChannel::drain`] this returns
+    /// immediately in the non-blocking modes, and gives up after the
+    /// [block timeout](UpChannel::set_block_timeout) if one is set.
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        if self.mode() != ChannelMode::BlockIfFull {
+            return Ok(());
+        }
+
+        let mut polls: u32 = 0;
+        while !self.is_empty() {
+            if let Some(max_polls) = self.block_timeout() {
+                if polls >= max_polls {
+                    break;
+                }
+                polls += 1;
+            }
+
+            yield_now().await;
+        }
+
+        Ok(())
+    }
+}
+
+impl embedded_io_async::Read for DownChannel {
+    /// Reads data from the channel, yielding to the executor until at least one byte is available.
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let count = DownChannel::read(self, buf);
+            if count > 0 {
+                return Ok(count);
+            }
+
+            yield_now().await;
+        }
+    }
+}
+
+/// Returns a future that completes the second time it's polled, after waking its task.
+fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtt::tests::channel;
+    use core::pin::pin;
+    use core::task::Waker;
+    use embedded_io_async::Write;
+
+    /// Polls `future` until it completes, and panics if it doesn't within a generous number of
+    /// polls.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        for _ in 0..1000 {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+
+        panic!("future didn't complete");
+    }
+
+    #[test]
+    fn write_completes_when_nothing_can_be_written() {
+        let mut up = unsafe { UpChannel::new(channel(16, ChannelMode::BlockIfFull), 0) };
+
+        up.set_muted(true);
+        assert_eq!(block_on(Write::write(&mut up, b"muted")), Ok(5));
+
+        up.set_muted(false);
+        up.set_block_timeout(Some(10));
+        assert_eq!(block_on(Write::write(&mut up, &[0; 20])), Ok(15));
+        assert_eq!(block_on(Write::write(&mut up, b"full")), Ok(4));
+    }
+}
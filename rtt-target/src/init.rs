@@ -16,9 +16,42 @@ macro_rules! rtt_init_repeat {
 macro_rules! rtt_init_channels {
     (
         $field:expr;
+        "down";
+        $index:expr;
+        $number:literal: { empty $(,)? }
+        $($tail:tt)*
+    ) => {
+        compile_error!(
+            "RTT down channels can't be declared as `empty`, only up channels can be set up at runtime"
+        );
+    };
+    (
+        $field:expr;
+        $direction:tt;
+        $index:expr;
+        $number:literal: { empty $(,)? }
+        $($tail:tt)*
+    ) => {
+        const _: () = assert!(
+            $number == $index,
+            concat!(
+                "RTT ", $direction, " channel numbers must start from 0 and not skip any numbers"
+            )
+        );
+
+        // The channel is left without a buffer, to be set up at runtime
+
+        $crate::rtt_init_channels!($field; $direction; $index + 1; $($tail)*);
+    };
+    (
+        $field:expr;
+        $direction:tt;
+        $index:expr;
         $number:literal: {
             size: $size:expr
+            $(, pow2: $pow2:literal )?
             $(, mode: $mode:path )?
+            $(, skip_per_write: $skip_per_write:literal )?
             $(, name: $name:literal )?
             $(,)?
         }
@@ -30,35 +63,178 @@ macro_rules! rtt_init_channels {
         let mut mode = $crate::ChannelMode::NoBlockSkip;
         $( mode = $mode; )?
 
+        const _: () = assert!(
+            $number == $index,
+            concat!(
+                "RTT ", $direction, " channel numbers must start from 0 and not skip any numbers"
+            )
+        );
+
+        // One byte of the buffer is always left free, so a smaller buffer can't hold any data
+        const _: () = {
+            let size: usize = $size;
+            assert!(
+                size >= 2,
+                "RTT buffer size must be at least 2 (use `empty` for a channel without a buffer)"
+            );
+        };
+
+        $(
+            const _: () = {
+                let size: usize = $size;
+                assert!(!$pow2 || size.is_power_of_two(), "RTT buffer size must be a power of two");
+            };
+        )?
+
         $field[$number].init(name, mode, {
-            static mut _RTT_CHANNEL_BUFFER: MaybeUninit<[u8; $size]> = MaybeUninit::uninit();
-            _RTT_CHANNEL_BUFFER.as_mut_ptr()
+            static mut _RTT_CHANNEL_BUFFER: RttAligned<MaybeUninit<[u8; $size]>> =
+                RttAligned(MaybeUninit::uninit());
+            _RTT_CHANNEL_BUFFER.0.as_mut_ptr()
         });
 
-        $crate::rtt_init_channels!($field; $($tail)*);
+        $crate::rtt_init_channels!($field; $direction; $index + 1; $($tail)*);
+    };
+    ($field:expr; $direction:tt; $index:expr;) => { };
+}
+
+/// rtt_init! implementation detail
+#[macro_export]
+#[doc(hidden)]
+macro_rules! rtt_init_aligned {
+    ($name:ident;) => {
+        #[repr(C)]
+        pub struct $name<T>(T);
+    };
+    ($name:ident; $align:literal) => {
+        #[repr(C, align($align))]
+        pub struct $name<T>(T);
+    };
+}
+
+/// rtt_init! implementation detail
+#[macro_export]
+#[doc(hidden)]
+macro_rules! rtt_init_symbol {
+    () => {
+        "_SEGGER_RTT"
+    };
+    ($symbol:literal) => {
+        $symbol
+    };
+}
+
+/// rtt_init! implementation detail
+#[macro_export]
+#[doc(hidden)]
+macro_rules! rtt_init_control_block {
+    (local; $ty:ty; $($section:literal)?; [$($noinit:tt)?]; $($symbol:literal)?) => {
+        $crate::rtt_init_placed! {
+            $($section)?; [$($noinit)?]; $($symbol)?;
+            static mut CONTROL_BLOCK: MaybeUninit<$ty> = MaybeUninit::uninit();
+        }
+    };
+    (; $ty:ty; $($section:literal)?; [$($noinit:tt)?]; $($symbol:literal)?) => {
+        $crate::rtt_init_placed! {
+            $($section)?; [$($noinit)?]; $($symbol)?;
+            #[used]
+            #[no_mangle]
+            #[export_name = $crate::rtt_init_symbol!($($symbol)?)]
+            pub static mut CONTROL_BLOCK: MaybeUninit<$ty> = MaybeUninit::uninit();
+        }
+
+        #[allow(unused)]
+        #[export_name = concat!($crate::rtt_init_symbol!($($symbol)?), "_rtt_init_must_not_be_called_multiple_times")]
+        fn rtt_init_must_not_be_called_multiple_times() { }
+    };
+}
+
+/// rtt_init! implementation detail
+#[macro_export]
+#[doc(hidden)]
+macro_rules! rtt_init_placed {
+    ($section:literal; [true]; $($symbol:literal)?; $item:item) => {
+        compile_error!("`section` and `noinit: true` can't be used together");
+        $item
+    };
+    ($section:literal; [$(false)?]; $($symbol:literal)?; $item:item) => {
+        #[link_section = $section]
+        $item
+    };
+    (; [true]; $($symbol:literal)?; $item:item) => {
+        #[link_section = concat!(".uninit.", $crate::rtt_init_symbol!($($symbol)?))]
+        $item
+    };
+    (; [$(false)?]; $($symbol:literal)?; $item:item) => {
+        $item
     };
-    ($field:expr;) => { };
 }
 
 /// rtt_init! implementation detail
 #[macro_export]
 #[doc(hidden)]
 macro_rules! rtt_init_wrappers {
-    ($field:expr; $cons:path; { $($acc:tt)* }; $n:literal: { $($_:tt)* } $($tail:tt)*) => {
+    (
+        $field:expr;
+        $direction:tt;
+        $cons:path;
+        { $($acc:tt)* };
+        $n:literal: { $($spec:tt)* }
+        $($tail:tt)*
+    ) => {
         $crate::rtt_init_wrappers!(
             $field;
+            $direction;
             $cons;
             {
                 $($acc)*
-                $cons(&mut $field[$n] as *mut _),
+                $crate::rtt_init_wrapper!(
+                    $direction;
+                    $cons(&mut $field[$n] as *mut _, $n);
+                    $($spec)*
+                ),
             };
             $($tail)*)
     };
-    ($field:expr; $cons:path; { $($acc:tt)* };) => {
+    ($field:expr; $direction:tt; $cons:path; { $($acc:tt)* };) => {
         ($($acc)*)
     };
 }
 
+/// rtt_init! implementation detail
+#[macro_export]
+#[doc(hidden)]
+macro_rules! rtt_init_wrapper {
+    (
+        "down";
+        $channel:expr;
+        size: $size:expr
+        $(, pow2: $pow2:literal )?
+        $(, mode: $mode:path )?
+        , skip_per_write: $skip_per_write:literal
+        $($_:tt)*
+    ) => {{
+        compile_error!("`skip_per_write` can only be used with RTT up channels");
+        $channel
+    }};
+    (
+        "up";
+        $channel:expr;
+        size: $size:expr
+        $(, pow2: $pow2:literal )?
+        $(, mode: $mode:path )?
+        , skip_per_write: $skip_per_write:literal
+        $($_:tt)*
+    ) => {{
+        // The setting is kept in the channel object, the control block has no place for it
+        let mut channel = $channel;
+        channel.set_skip_per_write($skip_per_write);
+        channel
+    }};
+    ($direction:tt; $channel:expr; $($_:tt)*) => {
+        $channel
+    };
+}
+
 /// Initializes RTT with the specified channels. Channel numbers, buffer sizes and names can be
 /// defined.
 ///
@@ -69,7 +245,9 @@ macro_rules! rtt_init_wrappers {
 ///     up: {
 ///         0: { // channel number
 ///             size: 1024, // buffer size in bytes
+///             pow2: true, // require a power of two size (optional, default: false)
 ///             mode: NoBlockSkip, // mode (optional, default: NoBlockSkip, see enum ChannelMode)
+///             skip_per_write: true, // see UpChannel::set_skip_per_write (optional, up only)
 ///             name: "Terminal" // name (optional, default: no name)
 ///         }
 ///         1: {
@@ -85,15 +263,148 @@ macro_rules! rtt_init_wrappers {
 /// };
 /// ```
 ///
-/// The channel numbers must start from 0 and not skip any numbers, or otherwise odd things will
-/// happen. The order of the channel parameters is fixed, but optional parameters can be left out.
-/// This macro should be called once within a function, preferably close to the start of your entry
-/// point. The macro must only be called once - if it's called twice in the same program a duplicate
-/// symbol error will occur.
+/// The buffer size can be any constant expression, such as a `const` item exported by a board
+/// support crate. It cannot refer to generic parameters of the surrounding function, because the
+/// buffers are `static` items and Rust doesn't allow statics to depend on an outer generic
+/// parameter. To let users of a reusable crate choose the size, wrap `rtt_init!` in a macro
+/// instead:
+///
+/// ```
+/// macro_rules! bsp_init_rtt {
+///     ($size:expr) => {
+///         rtt_target::rtt_init! {
+///             up: {
+///                 0: {
+///                     size: $size
+///                 }
+///             }
+///         }
+///     };
+/// }
+/// ```
+///
+/// The buffer size must be at least 2 bytes, because one byte is always left free. Smaller sizes,
+/// such as a mistyped `size: 0`, are rejected at compile time. An up channel that is declared as
+/// `1: { empty }` instead gets no buffer and is hidden from the host, so that a buffer can be set
+/// up for it at runtime with [`UpChannelBuilder`]. Down channels can't be declared as `empty`.
+///
+/// [`UpChannelBuilder`]: crate::UpChannelBuilder
+///
+/// With `pow2: true` the buffer size is checked at compile time, and sizes that are not a power of
+/// two are rejected with an error.
+///
+/// The `mode` of a channel is written into the control block as part of the initialization, before
+/// the channel buffer is set and before the channel objects are returned. There is no window in
+/// which the channel uses the default mode, so a `BlockIfFull` channel blocks from the first write
+/// on. It still drops data if a block timeout is set with [`UpChannel::set_block_timeout`] and the
+/// host doesn't read in time.
+///
+/// [`UpChannel::set_block_timeout`]: crate::UpChannel::set_block_timeout
+///
+/// Any number of channels can be declared in each direction, and the control block is sized to
+/// match. The channels are returned in tuples, so for example the eighth up channel is
+/// `channels.up.7`.
+///
+/// The channel numbers must start from 0 and not skip any numbers, so that they match the channel
+/// counts in the control block header. This is checked at compile time. The order of the channel
+/// parameters is fixed, but optional parameters can be left out. This macro should be called once
+/// within a function, preferably close to the start of your entry point. The macro must only be
+/// called once - if it's called twice in the same program a duplicate symbol error will occur.
 ///
 /// At compile time the macro will statically reserve space for the RTT control block and the
 /// channel buffers. At runtime the macro fills in the structures and prepares them for use.
 ///
+/// The control block can optionally be placed in a specific linker section, for example to put it
+/// at a fixed address so that the host doesn't have to scan memory for it:
+///
+/// ```
+/// let channels = rtt_init! {
+///     section: ".rtt"
+///     up: {
+///         0: {
+///             size: 1024
+///         }
+///     }
+/// };
+/// ```
+///
+/// The section has to be defined in your linker script. This only affects where the control
+/// block is placed; it is still exported as the `_SEGGER_RTT` symbol, so host tools that look up
+/// the symbol keep working. The channel buffers are not placed in the section.
+///
+/// Normally the control block is zeroed by the runtime at startup, together with other statics,
+/// so a debugger that attaches before the init macro has run doesn't find it. With `noinit: true`
+/// the control block is placed in the `.uninit._SEGGER_RTT` section instead (or `.uninit.` followed
+/// by the custom `symbol`), which the runtime leaves alone. After a reset the control block of the
+/// previous run is then still in memory, and the host can keep reading until the init macro clears
+/// and sets it up again:
+///
+/// ```
+/// let channels = rtt_init! {
+///     noinit: true
+///     up: {
+///         0: {
+///             size: 1024
+///         }
+///     }
+/// };
+/// ```
+///
+/// The section has to be placed in RAM by the linker script without being loaded or zeroed.
+/// `cortex-m-rt` does this for all `.uninit.*` sections, other runtimes may need an entry like
+/// `.uninit (NOLOAD) : { *(.uninit .uninit.*) } > RAM`. After a power cycle the section contains
+/// random data, and after flashing a different program the old control block may point to the wrong
+/// buffers, so host tools should only rely on it when attaching to the same program.
+/// `noinit: true` can't be combined with `section`, while `noinit: false` can. `noinit` must come
+/// after `section` and before `symbol` and `align` among the options.
+///
+/// The name of the exported control block symbol can be changed from the default `_SEGGER_RTT`,
+/// for example so that two libraries that each create their own control block can be linked into
+/// the same binary. The host tool then has to be told which symbol to look for. If both are given,
+/// `section` must come before `symbol`:
+///
+/// ```
+/// let channels = rtt_init! {
+///     symbol: "_MYLIB_RTT"
+///     up: {
+///         0: {
+///             size: 1024
+///         }
+///     }
+/// };
+/// ```
+///
+/// Some debug probes read memory faster if it is aligned. The control block and all channel
+/// buffers can be aligned to a given power of two number of bytes with `align`, which must come
+/// after `section` and `symbol` if they are also given:
+///
+/// ```
+/// let channels = rtt_init! {
+///     align: 32
+///     up: {
+///         0: {
+///             size: 1024
+///         }
+///     }
+/// };
+/// ```
+///
+/// Functions that find the control block by itself, such as [`UpChannel::conjure`],
+/// [`list_channels`], [`UpChannel::from_static_buffer`], [`DownChannel::from_static_buffer`] and
+/// [`UpChannelBuilder::build`], always use the `_SEGGER_RTT` symbol, so they don't work with a
+/// control block that has a different name.
+///
+/// [`UpChannel::conjure`]: crate::UpChannel::conjure
+/// [`list_channels`]: crate::list_channels
+/// [`UpChannel::from_static_buffer`]: crate::UpChannel::from_static_buffer
+/// [`DownChannel::from_static_buffer`]: crate::DownChannel::from_static_buffer
+/// [`UpChannelBuilder::build`]: crate::UpChannelBuilder::build
+///
+/// See [`rtt_init_local`] for a variant that doesn't export any symbols, for use inside library
+/// crates.
+///
+/// [`rtt_init_local`]: crate::rtt_init_local
+///
 /// The macro returns a generate struct that contains the channels. The struct for the example above
 /// would look as follows:
 ///
@@ -116,6 +427,11 @@ macro_rules! rtt_init_wrappers {
 #[macro_export]
 macro_rules! rtt_init {
     {
+        $(@$local:ident )?
+        $(section: $section:literal )?
+        $(noinit: $noinit:tt )?
+        $(symbol: $symbol:literal )?
+        $(align: $align:literal )?
         $(up: { $($up:tt)* } )?
         $(down: { $($down:tt)* } )?
     } => {{
@@ -126,6 +442,9 @@ macro_rules! rtt_init {
         use $crate::DownChannel;
         use $crate::rtt::*;
 
+        // Wrapper that applies the requested alignment to the control block and buffers
+        $crate::rtt_init_aligned!(RttAligned; $($align)?);
+
         #[repr(C)]
         pub struct RttControlBlock {
             header: RttHeader,
@@ -133,14 +452,13 @@ macro_rules! rtt_init {
             down_channels: [RttChannel; $crate::rtt_init_repeat!({ 1 + } { 0 }; $($($down)*)?)],
         }
 
-        #[used]
-        #[no_mangle]
-        #[export_name = "_SEGGER_RTT"]
-        pub static mut CONTROL_BLOCK: MaybeUninit<RttControlBlock> = MaybeUninit::uninit();
-
-        #[allow(unused)]
-        #[export_name = "rtt_init_must_not_be_called_multiple_times"]
-        fn rtt_init_must_not_be_called_multiple_times() { }
+        $crate::rtt_init_control_block!(
+            $($local)?;
+            RttAligned<RttControlBlock>;
+            $($section)?;
+            [$($noinit)?];
+            $($symbol)?
+        );
 
         use ::rtt_target::export::critical_section;
 
@@ -155,10 +473,10 @@ macro_rules! rtt_init {
         unsafe {
             ptr::write_bytes(CONTROL_BLOCK.as_mut_ptr(), 0, 1);
 
-            let cb = &mut *CONTROL_BLOCK.as_mut_ptr();
+            let cb = &mut (*CONTROL_BLOCK.as_mut_ptr()).0;
 
-            $( $crate::rtt_init_channels!(cb.up_channels; $($up)*); )?
-            $( $crate::rtt_init_channels!(cb.down_channels; $($down)*); )?
+            $( $crate::rtt_init_channels!(cb.up_channels; "up"; 0; $($up)*); )?
+            $( $crate::rtt_init_channels!(cb.down_channels; "down"; 0; $($down)*); )?
 
             // The header is initialized last to make it less likely an unfinished control block is
             // detected by the host.
@@ -170,14 +488,86 @@ macro_rules! rtt_init {
                 $( pub down: $crate::rtt_init_repeat!({ DownChannel, } {}; $($down)*), )?
             }
 
+            impl Channels {
+                /// Returns the address of the control block that contains these channels.
+                #[allow(unused, unused_unsafe)]
+                pub fn control_block_addr(&self) -> usize {
+                    unsafe { ptr::addr_of!(CONTROL_BLOCK) as usize }
+                }
+            }
+
             Channels {
-                $( up: $crate::rtt_init_wrappers!(cb.up_channels; UpChannel::new; {}; $($up)*), )?
-                $( down: $crate::rtt_init_wrappers!(cb.down_channels; DownChannel::new; {}; $($down)*), )?
+                $( up: $crate::rtt_init_wrappers!(cb.up_channels; "up"; UpChannel::new; {}; $($up)*), )?
+                $( down: $crate::rtt_init_wrappers!(cb.down_channels; "down"; DownChannel::new; {}; $($down)*), )?
             }
         }
     }};
 }
 
+/// Initializes RTT like [`rtt_init`], but without exporting the control block as a symbol.
+///
+/// [`rtt_init`] exports the control block as `_SEGGER_RTT`, so only one of them can exist in a
+/// program. This is a problem for a library crate that wants a control block of its own, for
+/// example for a driver that logs over RTT, because it would conflict with the control block of the
+/// application. This macro places the control block in an ordinary static instead, so any number of
+/// them can be used in the same program.
+///
+/// The syntax is the same as for [`rtt_init`], except that `symbol` is not allowed, and `noinit`
+/// places the control block in the `.uninit._SEGGER_RTT` section even though it has no symbol.
+/// Because there is no symbol, host tools can't look up the control block by name. They can still
+/// find it by scanning the memory, or the program can report its address from the
+/// `control_block_addr` method of the returned struct:
+///
+/// ```
+/// let channels = rtt_init_local! {
+///     up: {
+///         0: {
+///             size: 256,
+///             name: "Driver"
+///         }
+///     }
+/// };
+///
+/// let addr = channels.control_block_addr();
+/// ```
+///
+/// The functions that find the control block by the `_SEGGER_RTT` symbol, which are
+/// [`control_block_addr`], [`control_block_id`], [`list_channels`], [`set_all_up_modes`],
+/// [`UpChannel::conjure`], [`UpChannel::from_static_buffer`], [`DownChannel::from_static_buffer`]
+/// and [`UpChannelBuilder::build`], don't see a control block created with this macro. Calling
+/// them without [`rtt_init`] causes a linking error. The print macros
+/// don't use the symbol, so they work with a channel from this macro that is passed to
+/// [`set_print_channel`].
+///
+/// [`set_print_channel`]: crate::set_print_channel
+/// [`control_block_addr`]: crate::control_block_addr
+/// [`control_block_id`]: crate::control_block_id
+/// [`list_channels`]: crate::list_channels
+/// [`set_all_up_modes`]: crate::set_all_up_modes
+/// [`UpChannel::conjure`]: crate::UpChannel::conjure
+/// [`UpChannel::from_static_buffer`]: crate::UpChannel::from_static_buffer
+/// [`DownChannel::from_static_buffer`]: crate::DownChannel::from_static_buffer
+/// [`UpChannelBuilder::build`]: crate::UpChannelBuilder::build
+#[macro_export]
+macro_rules! rtt_init_local {
+    {
+        $(section: $section:literal )?
+        $(noinit: $noinit:tt )?
+        $(align: $align:literal )?
+        $(up: { $($up:tt)* } )?
+        $(down: { $($down:tt)* } )?
+    } => {
+        $crate::rtt_init! {
+            @local
+            $(section: $section )?
+            $(noinit: $noinit )?
+            $(align: $align )?
+            $(up: { $($up)* } )?
+            $(down: { $($down)* } )?
+        }
+    };
+}
+
 /// Initializes RTT with default up/down channels.
 ///
 /// The default channels are up channel 0 with a 1024 byte buffer and down channel 0 with a 16 byte
@@ -220,3 +610,45 @@ macro_rules! rtt_init_default {
         };
     };
 }
+
+/// Initializes RTT with a single up channel that follows the convention of terminal viewers such as
+/// the J-Link RTT Viewer, and returns the channel.
+///
+/// These tools show up channel 0 as text if it's called "Terminal", and may treat the channel as
+/// binary data or not show it at all otherwise. This macro sets up channel 0 with exactly that
+/// name, so that the output shows up as expected. The optional arguments specify the blocking
+/// mode (default: `NoBlockSkip`) and size of the buffer in bytes (default: 1024).
+///
+/// ```
+/// use core::fmt::Write;
+///
+/// let mut terminal = rtt_init_terminal!();
+/// writeln!(terminal, "Hello, world!").ok();
+/// ```
+///
+/// See [`rtt_init`] for more details.
+#[macro_export]
+macro_rules! rtt_init_terminal {
+    ($mode:path, $size:expr) => {{
+        let channels = $crate::rtt_init! {
+            up: {
+                0: {
+                    size: $size,
+                    mode: $mode,
+                    name: "Terminal"
+                }
+            }
+        };
+
+        channels.up.0
+    }};
+
+    ($mode:path) => {
+        $crate::rtt_init_terminal!($mode, 1024)
+    };
+
+    () => {{
+        use $crate::ChannelMode::NoBlockSkip;
+        $crate::rtt_init_terminal!(NoBlockSkip, 1024)
+    }};
+}
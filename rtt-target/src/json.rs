@@ -0,0 +1,41 @@
+use crate::UpChannel;
+use serde::Serialize;
+
+/// Size of the stack buffer used by [`UpChannel::write_json_line`].
+pub const JSON_BUFFER_SIZE: usize = 256;
+
+impl UpChannel {
+    /// Serializes `value` as JSON with `serde-json-core` and writes it to the channel followed by a
+    /// newline. Returns the number of bytes written, which is zero if the line was dropped.
+    ///
+    /// The object is serialized into a stack buffer of [`JSON_BUFFER_SIZE`] bytes first, and the
+    /// line is either written in full or not at all, so a host side line parser never sees a
+    /// truncated object. In `NoBlockTrim` mode lines that don't fit are dropped instead of trimmed.
+    /// In `BlockIfFull` mode the write blocks until the whole line has been written. With a
+    /// [block timeout](UpChannel::set_block_timeout) the line is dropped instead if the timeout
+    /// expires before there is room for all of it, see [`write_frame`](UpChannel::write_frame).
+    ///
+    /// If the JSON is larger than the buffer, [`serde_json_core::ser::Error::BufferFull`] is
+    /// returned and nothing is written. Use
+    /// [`write_json_line_with_buffer`](UpChannel::write_json_line_with_buffer) to supply a larger
+    /// buffer for such values.
+    pub fn write_json_line<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<usize, serde_json_core::ser::Error> {
+        let mut buf = [0u8; JSON_BUFFER_SIZE];
+        self.write_json_line_with_buffer(value, &mut buf)
+    }
+
+    /// Like [`write_json_line`](UpChannel::write_json_line) but serializes `value` into the
+    /// caller-supplied `buf`, which must be large enough for the JSON without the newline.
+    pub fn write_json_line_with_buffer<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+        buf: &mut [u8],
+    ) -> Result<usize, serde_json_core::ser::Error> {
+        let len = serde_json_core::to_slice(value, buf)?;
+
+        Ok(self.write_frame_parts(&[&buf[..len], b"\n"]).unwrap_or(0))
+    }
+}
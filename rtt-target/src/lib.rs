@@ -11,6 +11,42 @@
 //! access via its debug interface. The printing macros require a critical section which is
 //! platform-dependent.
 //!
+//! All locking goes through the [`critical-section`](https://docs.rs/critical-section) crate, so
+//! the printing macros work with any implementation of it: one provided by your architecture or
+//! HAL crate, or a custom one for multicore chips or an RTOS. For example on Cortex-M:
+//!
+//! ```toml
+//! [dependencies]
+//! cortex-m = { version = "0.7", features = ["critical-section-single-core"] }
+//! ```
+//!
+//! Or on single-hart RISC-V chips (HALs such as `esp-hal` already provide one):
+//!
+//! ```toml
+//! [dependencies]
+//! riscv = { version = "0.12", features = ["critical-section-single-hart"] }
+//! ```
+//!
+//! Or on MSP430, where the `msp430` crate disables and restores interrupts the same way as the
+//! Cortex-M implementation:
+//!
+//! ```toml
+//! [dependencies]
+//! msp430 = { version = "0.4", features = ["critical-section-single-core"] }
+//! ```
+//!
+//! On cores that have no critical section implementation at all, the `bare-unsafe-cs` feature
+//! provides one that does nothing, so that the printing macros can be used. **This is unsound**
+//! unless RTT is only ever used from a single execution context, such as `main` in a program
+//! without interrupt handlers that print. The feature provides the `critical-section`
+//! implementation for the whole program, so it can't be combined with another one; doing so results
+//! in a duplicate symbol error when linking.
+//!
+//! The pointer and size fields of the control block are as wide as `usize`. With the `ptr32`
+//! feature they are 32 bits wide on any target, for example to run 32-bit firmware as a 64-bit
+//! program in a simulator whose RTT reader expects the usual 32-bit layout. All channel buffers
+//! and names must then be located in the lowest 4 GiB of the address space.
+//!
 //! To interface with RTT from the host computer, a debug probe such as an ST-Link or J-Link is
 //! required. The normal debug protocol (e.g. SWD) is used to access RTT, so no extra connections
 //! such as SWO pins are needed.
@@ -25,6 +61,10 @@
 //! In an interrupt-based application with realtime constraints you could use a separate channel for
 //! every interrupt context to allow for lock-free logging.
 //!
+//! A single channel object must not be used from multiple contexts at once. If a channel has to be
+//! shared, wrap it in an [`AtomicUpChannel`], which uses an atomic flag instead of a critical
+//! section to keep writes from different contexts apart.
+//!
 //! # Channels and virtual terminals
 //!
 //! RTT supports multiple *channels* in both directions. Up channels go from target to host, and
@@ -55,6 +95,11 @@
 //! be lost. Channels can be set to blocking mode if this is desirable, however in that case the
 //! application will likely freeze when the buffer fills up if a debugger is not attached.
 //!
+//! With the `overflow-marker` feature enabled, an up channel writes [`OVERFLOW_MARKER`] in front of
+//! the next data after it has dropped data, so that the gap is visible on the host. The marker is
+//! meant for text, so it is left out of binary data such as the frames written by
+//! [`UpChannel::write_frame`] and defmt output, and written before the next text instead.
+//!
 //! The channel mode can also be changed on the fly by the debug probe. Therefore it may be
 //! advantageous to use a non-blocking mode in your microcontroller code, and set a blocking mode as
 //! needed when debugging. That way you will never end up with an application that freezes without a
@@ -90,6 +135,38 @@
 //! }
 //! ```
 //!
+//! # embedded-io integration
+//!
+//! With the `embedded-io` feature enabled, [`UpChannel`] implements [`embedded_io::Write`] and
+//! [`DownChannel`] implements [`embedded_io::Read`], so the channels can be used with any code that
+//! is generic over those traits.
+//!
+//! The `embedded-io-async` feature additionally implements the [`embedded_io_async`] traits. As
+//! RTT has no way to notify the target of host activity, waiting for buffer space or incoming data
+//! is done by yielding to the executor and polling again.
+//!
+//! # Binary framing
+//!
+//! [`UpChannel::write_frame`] sends binary data prefixed with its length. Frames are always written
+//! in full or not at all, so the host side parser doesn't get out of sync when the buffer is full.
+//!
+//! With the `crc` feature enabled, [`UpChannel::write_frame_crc`] additionally appends a checksum
+//! to each frame, so that the host can detect corrupted frames.
+//!
+//! With the `cobs` feature enabled, [`UpChannel::write_cobs_frame`] sends binary data as zero
+//! delimited COBS frames, so the host side can always find the start of the next frame even if
+//! some frames were dropped.
+//!
+//! With the `deferred` feature enabled, the [`rtt_defer`] macro sends log messages as a
+//! [`DeferredRecord`] that contains a format string ID and the raw argument values, and the host
+//! does the formatting. This is similar to `defmt`, but much simpler.
+//!
+//! With the `postcard` feature enabled, [`UpChannel::write_postcard`] serializes a value with
+//! [`postcard`] and writes it as a single message that is never split.
+//!
+//! With the `serde-json-core` feature enabled, [`UpChannel::write_json_line`] writes a value as a
+//! single line of JSON for easy parsing by host side scripts.
+//!
 //! # Plain Printing
 //!
 //! For no-hassle output the [`rprint`] and [`rprintln`] macros are provided. They use a single down
@@ -97,6 +174,12 @@
 //! therefore work exactly like the standard `println` style macros. They can be used from any
 //! context. The [`rtt_init_print`] convenience macro initializes printing on channel 0.
 //!
+//! The [`urprint`] and [`urprintln`] macros work the same way but format with `ufmt`, which results
+//! in smaller code size.
+//!
+//! The [`reprint`] and [`reprintln`] macros print to a separate error print channel, similarly to
+//! the standard error stream.
+//!
 //! ```
 //! use rtt_target::{rtt_init_print, rprintln};
 //!
@@ -129,11 +212,32 @@
 //! }
 //! ```
 //!
-//! The macros also support an extended syntax to print to different RTT virtual terminals.
+//! Alternatively, the `disable-printing` feature removes the output of [`rprint`] and [`rprintln`]
+//! and the macros based on them in all builds, without evaluating their arguments. The other
+//! print macros, such as [`rprintln_blocking`], [`urprintln`], [`rprintln_nocs`] and
+//! [`rhexdump`], don't print anything either with this feature.
+//!
+//! The macros also support an extended syntax to print to different RTT virtual terminals, and to
+//! select one of several print channels set with [`set_print_channel_at`].
 //!
 //! Please note that because a critical section is used, printing into a blocking channel will cause
 //! the application to block and freeze when the buffer is full.
 //!
+//! # Panics
+//!
+//! A panic handler that prints the panic message over RTT and halts is provided by the companion
+//! crate [`panic-rtt-target`](https://docs.rs/panic-rtt-target). It writes the message to the
+//! print channel in `BlockIfFull` mode, as well as to the panic channel set with
+//! [`set_panic_channel`] and the defmt channel if they are used, and then loops forever. Add it as
+//! a dependency and link it in with `use panic_rtt_target as _;` instead of defining your own
+//! `#[panic_handler]`. A custom handler can use [`with_panic_channel`] to do the same.
+//!
+//! Alternatively, the `panic-handler` feature of this crate provides the same handler without an
+//! extra dependency. It also flushes the print channel before halting. With the feature enabled
+//! the program must not define its own `#[panic_handler]` or use `panic-rtt-target`, as only one
+//! panic handler can exist in a program. With the feature disabled, which is the default, this
+//! crate defines no panic handler.
+//!
 //! # Reading
 //!
 //! The following example shows how to set up the RTT to read simple input sent from the host
@@ -159,31 +263,72 @@
 #![no_std]
 #![cfg_attr(docsrs, feature(doc_cfg), feature(doc_auto_cfg))]
 
-use core::convert::Infallible;
+#[cfg(test)]
+extern crate std;
+
+use core::cmp::min;
+use core::convert::{Infallible, TryFrom};
+use core::ffi::CStr;
 use core::fmt;
+use core::ops::{Deref, DerefMut};
 use ufmt_write::uWrite;
 
+#[cfg(all(target_has_atomic = "8", target_has_atomic = "ptr"))]
+mod atomic;
+#[cfg(feature = "bare-unsafe-cs")]
+mod bare_cs;
+#[cfg(feature = "cobs")]
+mod cobs;
+#[cfg(feature = "crc")]
+mod crc;
 #[doc(hidden)]
 /// Public due to access from macro
 pub mod debug;
+#[cfg(feature = "deferred")]
+mod deferred;
 #[cfg(feature = "defmt")]
 mod defmt;
+#[cfg(feature = "embedded-io")]
+mod embedded_io;
+#[cfg(feature = "embedded-io-async")]
+mod embedded_io_async;
+#[cfg(feature = "serde-json-core")]
+mod json;
 #[cfg(feature = "log")]
 mod log;
+#[cfg(feature = "postcard")]
+mod postcard;
 /// Public due to access from macro
 #[doc(hidden)]
 pub mod rtt;
 
 mod init;
+mod panic;
 mod print;
 
+#[cfg(all(target_has_atomic = "8", target_has_atomic = "ptr"))]
+pub use atomic::AtomicUpChannel;
+pub use panic::{set_panic_channel, with_panic_channel};
 pub use print::*;
+pub use rtt::{
+    control_block_addr, control_block_id, list_channels, set_all_up_modes, set_block_wait_hook,
+    ChannelInfo, Direction, WriteGuard,
+};
 
 #[cfg(feature = "defmt")]
-pub use defmt::set_defmt_channel;
+pub use defmt::{set_defmt_channel, write_defmt_frame};
 
+#[cfg(feature = "deferred")]
+pub use deferred::{deferred_id, DeferredArg, DeferredRecord, DEFERRED_MAX_ARGS_SIZE};
+
+#[cfg(feature = "postcard")]
+pub use crate::postcard::POSTCARD_BUFFER_SIZE;
+#[cfg(feature = "serde-json-core")]
+pub use json::JSON_BUFFER_SIZE;
 #[cfg(feature = "log")]
 pub use log::*;
+#[cfg(feature = "overflow-marker")]
+pub use rtt::OVERFLOW_MARKER;
 
 /// RTT up (target to host) channel
 ///
@@ -192,30 +337,343 @@ pub use log::*;
 ///
 /// Note that the formatted writing implementations diverge slightly from the trait definitions in
 /// that if the channel is in non-blocking mode, writing will *not* block.
-pub struct UpChannel(*mut rtt::RttChannel);
+pub struct UpChannel {
+    channel: *mut rtt::RttChannel,
+    number: usize,
+    block_timeout: Option<u32>,
+    muted: bool,
+    skip_per_write: bool,
+    stats: rtt::Stats,
+    last_read: usize,
+}
 
 unsafe impl Send for UpChannel {}
 
 impl UpChannel {
     /// Public due to access from macro.
     #[doc(hidden)]
-    pub unsafe fn new(channel: *mut rtt::RttChannel) -> Self {
-        UpChannel(channel)
+    pub unsafe fn new(channel: *mut rtt::RttChannel, number: usize) -> Self {
+        UpChannel {
+            channel,
+            number,
+            block_timeout: None,
+            muted: false,
+            skip_per_write: false,
+            stats: rtt::Stats::default(),
+            last_read: (*channel).read_pointers().1,
+        }
     }
 
     #[allow(clippy::mut_from_ref)]
     fn channel(&self) -> &mut rtt::RttChannel {
-        unsafe { &mut *self.0 }
+        unsafe { &mut *self.channel }
+    }
+
+    /// Returns the mode to use for `len` bytes of data that must be written either in full or not
+    /// at all.
+    ///
+    /// A `BlockIfFull` write makes the data written so far visible to the host while it waits, so
+    /// if it gave up on a block timeout halfway, the host would see part of the data. With a block
+    /// timeout this therefore waits for room for all of the data up front instead, and returns
+    /// `NoBlockSkip` so that the data is dropped as a whole if the wait timed out.
+    fn frame_mode(&self, len: usize) -> ChannelMode {
+        match (self.mode(), self.block_timeout) {
+            (ChannelMode::BlockIfFull, None) => ChannelMode::BlockIfFull,
+            (ChannelMode::BlockIfFull, Some(max_spins)) if !self.muted => {
+                let mut free = self.writable_len();
+                let mut spins = 0;
+
+                while free < len && spins < max_spins {
+                    rtt::block_wait();
+
+                    // Like a blocking write, only give up once the host stops freeing space
+                    let now = self.writable_len();
+                    spins = if now > free { 0 } else { spins + 1 };
+                    free = now;
+                }
+
+                ChannelMode::NoBlockSkip
+            }
+            _ => ChannelMode::NoBlockSkip,
+        }
+    }
+
+    fn writer(&self) -> rtt::RttWriter<'_> {
+        self.channel()
+            .writer(&self.stats)
+            .block_timeout(self.block_timeout)
+            .muted(self.muted)
+            .skip_per_write(self.skip_per_write)
+    }
+
+    /// Returns the free space that is left for data in the next write, after the overflow marker
+    /// that may have to be written first.
+    fn writable_data_len(&self) -> usize {
+        self.writable_len()
+            .saturating_sub(self.stats.pending_marker_len())
+    }
+
+    /// Returns a writer for binary data, which never gets an overflow marker written into it.
+    fn binary_writer(&self) -> rtt::RttWriter<'_> {
+        self.writer().binary()
+    }
+
+    /// Returns the number of the channel.
+    pub fn number(&self) -> usize {
+        self.number
+    }
+
+    /// Returns the name of the channel as stored in the control block, or `None` if the channel
+    /// has no name or the name is not valid UTF-8.
+    pub fn name(&self) -> Option<&str> {
+        self.channel().name()
+    }
+
+    /// Returns the size of the channel buffer in bytes, or 0 if the channel has no buffer. One byte
+    /// of the buffer is always left free, so at most `buffer_len() - 1` bytes can be waiting for
+    /// the host at once.
+    pub fn buffer_len(&self) -> usize {
+        self.channel().size()
     }
 
     /// Writes `buf` to the channel and returns the number of bytes written. Behavior when the
     /// buffer is full is subject to the channel blocking mode.
+    ///
+    /// `buf` may be larger than the whole channel buffer. In `BlockIfFull` mode the data is then
+    /// written in pieces as the host reads the buffer, so the write completes as long as the host
+    /// keeps reading. In `NoBlockTrim` mode as much as currently fits is written, and in the
+    /// skipping modes nothing is written, because the data could never fit in full.
     pub fn write(&mut self, buf: &[u8]) -> usize {
-        let mut writer = self.channel().writer();
+        let mut writer = self.writer();
         writer.write(buf);
         writer.commit()
     }
 
+    /// Writes as much of `buf` as fits in the channel and returns the part that didn't fit, for
+    /// example to queue it and retry later.
+    ///
+    /// In the non-blocking modes this writes what currently fits like `NoBlockTrim`, and the part
+    /// that is returned is not counted as dropped data. In `BlockIfFull` mode this waits for
+    /// space, so the returned slice is empty unless the [block
+    /// timeout](UpChannel::set_block_timeout) expires.
+    ///
+    /// ```
+    /// pending = channels.up.1.write_remaining(pending);
+    /// ```
+    pub fn write_remaining<'a>(&mut self, buf: &'a [u8]) -> &'a [u8] {
+        let (mode, len) = if self.is_blocking() {
+            (ChannelMode::BlockIfFull, buf.len())
+        } else {
+            // Only pass on what fits, so that the rest doesn't end up in the dropped data
+            (
+                ChannelMode::NoBlockTrim,
+                min(self.writable_data_len(), buf.len()),
+            )
+        };
+
+        let mut writer = self.writer();
+        writer.write_with_mode(mode, &buf[..len]);
+
+        &buf[writer.commit()..]
+    }
+
+    /// Writes a single byte to the channel and returns true if it was written. Behavior when the
+    /// buffer is full is subject to the channel blocking mode, like with
+    /// [`write`](UpChannel::write), except that the non-blocking modes all drop the byte.
+    ///
+    /// This is faster than writing a one byte slice, which makes it useful for things like markers
+    /// or protocols that send one byte at a time.
+    pub fn write_byte(&mut self, byte: u8) -> bool {
+        if self.muted {
+            return false;
+        }
+
+        self.channel()
+            .write_byte(&self.stats, byte, self.block_timeout)
+    }
+
+    /// Writes `buf` to the channel without checking for free space, ignoring the blocking mode,
+    /// the block timeout and [`set_muted`](UpChannel::set_muted). This is the bare copy into the
+    /// ring buffer, for programs that manage the space themselves, for example with
+    /// [`wait_for_space`](UpChannel::wait_for_space), or to measure the cost of the copy.
+    ///
+    /// # Safety
+    ///
+    /// `buf` must not be longer than [`writable_len`](UpChannel::writable_len), and the channel
+    /// must have a buffer. Writing more than the free space overwrites data that the host hasn't
+    /// read yet and leaves the write pointer past the read pointer, which corrupts the ring buffer.
+    /// Because the host only ever frees more space, checking `writable_len` beforehand is enough,
+    /// as long as nothing else writes to the channel in between.
+    pub unsafe fn write_unchecked(&mut self, buf: &[u8]) {
+        self.channel().write_unchecked(&self.stats, buf);
+    }
+
+    /// Writes `s` to the channel and returns true if all of it was written, or false if any of it
+    /// was dropped. Behavior when the buffer is full is subject to the channel blocking mode.
+    ///
+    /// In `NoBlockTrim` mode the string is cut at a character boundary, so that the output never
+    /// ends in the middle of a multi-byte UTF-8 character.
+    pub fn write_str_truncated(&mut self, s: &str) -> bool {
+        let mut len = s.len();
+
+        if self.mode() == ChannelMode::NoBlockTrim {
+            len = min(self.writable_data_len(), len);
+            while !s.is_char_boundary(len) {
+                len -= 1;
+            }
+        }
+
+        let written = self.write(&s.as_bytes()[..len]);
+
+        // The part that was cut off is dropped just like trimmed data, unless the channel is muted
+        if !self.muted {
+            self.stats.add_dropped(s.len() - len);
+        }
+
+        written == s.len()
+    }
+
+    /// Writes all slices in `bufs` back to back and returns the total number of bytes written. The
+    /// slices are treated as one write of their combined length, and the blocking mode applies to
+    /// the whole operation. For example in `NoBlockSkip` mode either all slices are written or none
+    /// of them.
+    ///
+    /// In the non-blocking modes the write pointer is only updated once at the end, so the host
+    /// sees the slices appear together. In `BlockIfFull` mode the write pointer is also updated
+    /// every time the buffer is full, so that the host can read the data written so far and make
+    /// room for the rest. The host may then see the slices arrive in several parts, but still in
+    /// order and without gaps.
+    ///
+    /// ```
+    /// channels.up.0.write_vectored(&[b"id=", id_str.as_bytes(), b"\n"]);
+    /// ```
+    pub fn write_vectored(&mut self, bufs: &[&[u8]]) -> usize {
+        let mode = self.mode();
+        let mut writer = self.writer();
+        for buf in bufs {
+            writer.write_with_mode(mode, buf);
+        }
+        writer.commit()
+    }
+
+    /// Writes all of `buf` to the channel, or returns `Err` with the number of bytes that were
+    /// written if that was not possible.
+    ///
+    /// In `BlockIfFull` mode this blocks until everything has been written, so it only fails if a
+    /// [block timeout](UpChannel::set_block_timeout) expires. In the non-blocking modes it fails if
+    /// the buffer doesn't have room for all of `buf`, in which case the count is the part that was
+    /// written according to the mode (zero in `NoBlockSkip` mode).
+    pub fn write_all(&mut self, buf: &[u8]) -> Result<(), usize> {
+        let written = self.write(buf);
+
+        if written == buf.len() {
+            Ok(())
+        } else {
+            Err(written)
+        }
+    }
+
+    /// Writes `buf` to the channel and returns the number of bytes written, or an error if the
+    /// channel is in [`NoBlockSkip`](ChannelMode::NoBlockSkip) mode and the buffer doesn't have
+    /// enough space for all of `buf`. In that case nothing is written. In the other modes this
+    /// behaves exactly like [`write`](UpChannel::write).
+    ///
+    /// This method does not enter a critical section, so it can be used from within one.
+    pub fn try_write(&mut self, buf: &[u8]) -> Result<usize, WouldBlockError> {
+        if self.muted {
+            return Ok(0);
+        }
+
+        let mode = self.mode();
+        let mut writer = self.writer();
+        writer.write_with_mode(mode, buf);
+
+        if writer.is_skipped() {
+            return Err(WouldBlockError);
+        }
+
+        Ok(writer.commit())
+    }
+
+    /// Writes `buf` to the channel as a frame prefixed with its length as a little-endian `u32`.
+    /// Returns the number of bytes written including the header, or an error if the frame was
+    /// dropped.
+    ///
+    /// The header and the payload are written together, so the frame is either written in full or
+    /// not at all and the host side parser can't get out of sync. In `NoBlockTrim` mode frames
+    /// that don't fit are dropped instead of trimmed. In `BlockIfFull` mode the write blocks until
+    /// the whole frame has been written. If a [block timeout](UpChannel::set_block_timeout) is set,
+    /// the write instead waits for room for the whole frame before writing any of it, and the frame
+    /// is dropped if the timeout expires first. A frame that is larger than the buffer is then
+    /// always dropped. With the `overflow-marker` feature, no marker is written in front of a
+    /// frame.
+    pub fn write_frame(&mut self, buf: &[u8]) -> Result<usize, WouldBlockError> {
+        self.write_frame_parts(&[&(buf.len() as u32).to_le_bytes(), buf])
+    }
+
+    /// Writes `parts` back to back as a single frame, either in full or not at all.
+    fn write_frame_parts(&mut self, parts: &[&[u8]]) -> Result<usize, WouldBlockError> {
+        if self.muted {
+            return Ok(0);
+        }
+
+        let mode = self.frame_mode(parts.iter().map(|part| part.len()).sum());
+        let mut writer = self.binary_writer();
+        for part in parts {
+            writer.write_with_mode(mode, part);
+        }
+
+        if writer.is_skipped() {
+            return Err(WouldBlockError);
+        }
+
+        Ok(writer.commit())
+    }
+
+    /// Writes formatted data to the channel within a critical section and returns the number of
+    /// bytes written. Behavior when the buffer is full is subject to the channel blocking mode.
+    ///
+    /// Unlike [`write!`], this keeps other contexts from writing to the same buffer while
+    /// formatting, for example through a channel obtained with [`conjure`](UpChannel::conjure) in
+    /// an interrupt handler. In `BlockIfFull` mode the critical section is held until the host has
+    /// read enough data for the write to complete.
+    ///
+    /// ```
+    /// channels.up.1.write_fmt_cs(format_args!("temperature: {}", temp));
+    /// ```
+    pub fn write_fmt_cs(&mut self, args: fmt::Arguments<'_>) -> usize {
+        critical_section::with(|_| {
+            let mut writer = self.writer();
+            fmt::Write::write_fmt(&mut writer, args).ok();
+            writer.commit()
+        })
+    }
+
+    /// Reserves `len` bytes of space in the channel buffer that can be filled in directly, for
+    /// example by DMA, without copying the data with [`write`](UpChannel::write). Returns `None`
+    /// if there isn't enough space. This method never blocks, regardless of the channel mode.
+    ///
+    /// The reserved space is always contiguous. Free space is split in two at the wrap point of the
+    /// ring buffer, so this may fail even if [`writable_len`](UpChannel::writable_len) is large
+    /// enough; in that case a shorter reservation gives the space up to the wrap point, after which
+    /// the space at the start of the buffer can be reserved.
+    ///
+    /// The data is made visible to the host when the returned guard is dropped. Use
+    /// [`WriteGuard::truncate`] to write less than was reserved.
+    ///
+    /// ```
+    /// if let Some(mut guard) = channels.up.1.reserve(64) {
+    ///     guard.copy_from_slice(&samples);
+    /// }
+    /// ```
+    pub fn reserve(&mut self, len: usize) -> Option<WriteGuard<'_>> {
+        if self.muted {
+            return None;
+        }
+
+        self.channel().reserve(&self.stats, len)
+    }
+
     /// Creates a writer for formatted writing with ufmt.
     ///
     /// The correct way to use this method is to call it once for each write operation. This is so
@@ -225,8 +683,8 @@ impl UpChannel {
     /// let mut output = channels.up.0;
     /// uwriteln!(output.u(), "Hello, ufmt!");
     /// ```
-    pub fn u(&mut self) -> uWriter {
-        uWriter(self.channel().writer())
+    pub fn u(&mut self) -> uWriter<'_> {
+        uWriter(self.writer())
     }
 
     /// Gets the current blocking mode of the channel. The default is `NoBlockSkip`.
@@ -234,11 +692,176 @@ impl UpChannel {
         self.channel().mode()
     }
 
+    /// Returns true if the channel is in `BlockIfFull` mode, i.e. a write may wait for the host to
+    /// read data. This only compares the mode bits, so it's cheaper than [`mode`](UpChannel::mode).
+    pub fn is_blocking(&self) -> bool {
+        self.channel().is_blocking()
+    }
+
     /// Sets the blocking mode of the channel
     pub fn set_mode(&mut self, mode: ChannelMode) {
         self.channel().set_mode(mode)
     }
 
+    /// Sets the blocking mode of the channel until the returned guard is dropped, after which the
+    /// previous mode is restored. The channel is used through the guard in the meantime.
+    ///
+    /// ```
+    /// let mut up = channels.up.0.mode_scope(ChannelMode::BlockIfFull);
+    /// up.write(&firmware_chunk);
+    /// // The previous mode is restored here
+    /// ```
+    pub fn mode_scope(&mut self, mode: ChannelMode) -> ModeGuard<'_> {
+        let previous = self.mode();
+        self.set_mode(mode);
+
+        ModeGuard {
+            channel: self,
+            previous,
+        }
+    }
+
+    /// Gets the raw flags word of the channel from the control block. The lowest two bits are the
+    /// blocking mode, see [`mode`](UpChannel::mode). The other bits are reserved by the RTT
+    /// protocol and are zero unless set with [`set_flags_raw`](UpChannel::set_flags_raw).
+    pub fn flags_raw(&self) -> usize {
+        self.channel().flags()
+    }
+
+    /// Sets the raw flags word of the channel in the control block, for host tools that expect
+    /// specific values in the reserved bits. The lowest two bits of `flags` are ignored so that
+    /// the blocking mode isn't changed by accident; use [`set_mode`](UpChannel::set_mode) to
+    /// change it.
+    pub fn set_flags_raw(&mut self, flags: usize) {
+        self.channel().set_flags_raw(flags)
+    }
+
+    /// Gets the maximum number of polls a write waits for buffer space in `BlockIfFull` mode. The
+    /// default is `None`, which means waiting indefinitely.
+    pub fn block_timeout(&self) -> Option<u32> {
+        self.block_timeout
+    }
+
+    /// Limits how long a write waits for buffer space in `BlockIfFull` mode. If the host hasn't
+    /// freed any space after `max_spins` consecutive polls, the rest of the write is dropped as
+    /// in `NoBlockTrim` mode. This keeps the application running if the debugger is detached
+    /// while the channel is in blocking mode. Each write operation waits for the full timeout
+    /// again.
+    ///
+    /// The timeout is stored in this channel object rather than the control block, so it is not
+    /// visible to the host. Pass `None` to wait indefinitely.
+    pub fn set_block_timeout(&mut self, max_spins: Option<u32>) {
+        self.block_timeout = max_spins;
+    }
+
+    /// Returns true if writes in `NoBlockSkip` mode are skipped separately, see
+    /// [`set_skip_per_write`](UpChannel::set_skip_per_write).
+    pub fn skip_per_write(&self) -> bool {
+        self.skip_per_write
+    }
+
+    /// Changes what is skipped in `NoBlockSkip` mode when the buffer is full. Normally a formatted
+    /// write such as `writeln!` is skipped completely if it doesn't fit. With `skip_per_write` set,
+    /// each piece of formatted output and each `write` call is skipped on its own, and once one
+    /// doesn't fit the rest of the operation is dropped. This keeps the beginning of a message
+    /// that doesn't fit completely, without cutting text in the middle of a character like
+    /// `NoBlockTrim` does.
+    ///
+    /// The RTT protocol has no such mode, so like the block timeout this is stored in the channel
+    /// object and not visible to the host. Channel objects created with
+    /// [`conjure`](UpChannel::conjure) or [`from_static_buffer`](UpChannel::from_static_buffer)
+    /// start out with it unset. Frame writes and [`write_vectored`](UpChannel::write_vectored) are
+    /// always skipped as a whole.
+    pub fn set_skip_per_write(&mut self, skip_per_write: bool) {
+        self.skip_per_write = skip_per_write;
+    }
+
+    /// Returns true if the channel is muted with [`set_muted`](UpChannel::set_muted).
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    /// Mutes or unmutes the channel. While muted, all writes through this channel object return
+    /// without touching the buffer and report 0 bytes written, for example to silence output
+    /// during a time critical section. This is cheaper than switching to a non-blocking mode, and
+    /// the discarded data isn't counted as dropped by the statistics.
+    ///
+    /// The setting only applies to this channel object, not to other objects for the same buffer.
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    /// Returns the number of bytes that were dropped because they didn't fit in the buffer,
+    /// saturating at `u64::MAX`.
+    ///
+    /// The count is kept in this channel object, so a channel created with
+    /// [`conjure`](UpChannel::conjure) starts counting from zero.
+    #[cfg(feature = "stats")]
+    pub fn dropped_bytes(&self) -> u64 {
+        self.stats.dropped()
+    }
+
+    /// Returns the number of bytes that have been written to the buffer through this channel
+    /// object, saturating at `u64::MAX`. Together with
+    /// [`dropped_bytes`](UpChannel::dropped_bytes) this shows the throughput and loss of the
+    /// channel. Data written by other channel objects for the same buffer is not included.
+    #[cfg(feature = "stats")]
+    pub fn total_written(&self) -> u64 {
+        self.stats.written()
+    }
+
+    /// Returns the highest number of unread bytes that were in the buffer after a write through
+    /// this channel object. A value close to the buffer size means the buffer was nearly full at
+    /// some point, i.e. the host didn't keep up.
+    ///
+    /// Like [`dropped_bytes`](UpChannel::dropped_bytes) this is kept in the channel object.
+    #[cfg(feature = "stats")]
+    pub fn high_water_mark(&self) -> usize {
+        self.stats.high_water_mark()
+    }
+
+    /// Returns the statistics of the channel and resets them to zero, so that the next call only
+    /// covers the time since this one. This is useful for periodic reporting of transfer rates.
+    #[cfg(feature = "stats")]
+    pub fn take_stats(&mut self) -> ChannelStats {
+        self.stats.take()
+    }
+
+    /// Returns true if the host has read from the channel since the last call, which indicates that
+    /// a debugger is attached and actively reading.
+    ///
+    /// RTT has no real attach signal, so this is only a heuristic. It has to be polled periodically,
+    /// and it only detects a host if there was unread data in the buffer between calls: nothing
+    /// written since the last call means nothing to read, and the result will be false even if a
+    /// host is attached. The first call compares against the state when the channel object was
+    /// created.
+    pub fn host_attached_heuristic(&mut self) -> bool {
+        let read = self.channel().read_pointers().1;
+        let attached = read != self.last_read;
+        self.last_read = read;
+
+        attached
+    }
+
+    /// Returns the current write pointer of the channel, i.e. the offset in the buffer where the
+    /// next byte will be written.
+    ///
+    /// The pointers are read atomically from the control block, but the value is only a snapshot:
+    /// it changes as soon as anything is written to the channel.
+    pub fn write_pos(&self) -> usize {
+        self.channel().read_pointers().0
+    }
+
+    /// Returns the current read pointer of the channel, i.e. the offset in the buffer of the next
+    /// byte the host will read.
+    ///
+    /// The read pointer is updated by the host, so the value may be stale by the time it's used,
+    /// and it doesn't change at all if no host is attached. Together with
+    /// [`write_pos`](UpChannel::write_pos) it can be used to report how full the buffer is.
+    pub fn read_pos(&self) -> usize {
+        self.channel().read_pointers().1
+    }
+
     /// Converts the channel into a virtual terminal that can be used for writing into multiple
     /// virtual terminals.
     pub fn into_terminal(self) -> TerminalChannel {
@@ -258,12 +881,7 @@ impl UpChannel {
     /// besides the returned object during or after calling this function. Essentially this function
     /// is only safe to use in panic handlers and the like that permanently disable interrupts.
     pub unsafe fn conjure(number: usize) -> Option<UpChannel> {
-        extern "C" {
-            #[link_name = "_SEGGER_RTT"]
-            static mut CONTROL_BLOCK: rtt::RttHeader;
-        }
-
-        let control_block = core::ptr::addr_of_mut!(CONTROL_BLOCK);
+        let control_block = rtt::control_block();
         if number >= (*control_block).max_up_channels() {
             return None;
         }
@@ -278,7 +896,90 @@ impl UpChannel {
             return None;
         }
 
-        Some(UpChannel(ptr))
+        Some(UpChannel::new(ptr, number))
+    }
+
+    /// Creates an up channel that uses `buf` as its buffer instead of the one reserved by
+    /// `rtt_init`, so the buffer size can be chosen at runtime. `name` replaces the name of the
+    /// channel. Returns `None` if the channel number is too high, if the channel has not been
+    /// initialized, or if `buf` is shorter than 2 bytes. A ring buffer always keeps one byte free,
+    /// so smaller buffers could never hold any data.
+    ///
+    /// The channel must still be declared in `rtt_init` so that the control block has room for it.
+    /// Declare it with a small size such as `size: 2` to avoid reserving a buffer that is never
+    /// used, and use this function instead of the channel returned by the macro:
+    ///
+    /// ```
+    /// static mut BUFFER: [u8; 4096] = [0; 4096];
+    ///
+    /// rtt_init! { up: { 0: { size: 2 } } };
+    ///
+    /// let size = if has_large_ram() { 4096 } else { 512 };
+    /// let name = CStr::from_bytes_with_nul(b"Terminal\0").unwrap();
+    /// let channel = unsafe { UpChannel::from_static_buffer(0, Some(name), &mut BUFFER[..size]) };
+    /// ```
+    ///
+    /// Calling this function will cause a linking error if `rtt_init` has not been called.
+    ///
+    /// # Safety
+    ///
+    /// This function must only be called after `rtt_init` has been called. `buf` must not be
+    /// accessed through anything else than the returned channel for the rest of the program.
+    ///
+    /// It's undefined behavior for something else to access the channel through anything else
+    /// besides the returned object during or after calling this function, including the channel
+    /// returned by `rtt_init`.
+    pub unsafe fn from_static_buffer(
+        number: usize,
+        name: Option<&'static CStr>,
+        buf: &'static mut [u8],
+    ) -> Option<UpChannel> {
+        if buf.len() < 2 {
+            return None;
+        }
+
+        let ptr = rtt::channel_ptr(rtt::Direction::Up, number)?;
+        if !(*ptr).is_initialized() {
+            return None;
+        }
+
+        (*ptr).replace_buffer(name, (*ptr).mode(), buf);
+
+        Some(UpChannel::new(ptr, number))
+    }
+
+    /// Returns the number of bytes that can currently be written to the channel without blocking
+    /// or dropping data.
+    ///
+    /// This is the total free space in the ring buffer, including the space after the wrap point,
+    /// so a single write of up to this many bytes will fit in its entirety. The value may grow at
+    /// any time as the host reads from the buffer, but it will not shrink unless something else
+    /// writes to the channel.
+    pub fn writable_len(&self) -> usize {
+        self.channel().writable_len()
+    }
+
+    /// Returns how full the buffer currently is as a percentage from 0 to 100, i.e. how much of the
+    /// written data the host hasn't read yet. 100 means that nothing more can be written until the
+    /// host reads some data. Returns 0 for a channel without a buffer.
+    ///
+    /// ```
+    /// if channels.up.0.fill_percent() > 90 {
+    ///     // The host isn't keeping up, reduce the amount of logging
+    /// }
+    /// ```
+    pub fn fill_percent(&self) -> u8 {
+        self.channel().fill_percent()
+    }
+
+    /// Discards all data in the buffer that the host hasn't read yet.
+    ///
+    /// The read pointer of an up channel normally belongs to the host, so this races with a host
+    /// that is reading at the same time: the host may still deliver part of the discarded data, or
+    /// overwrite the read pointer with its own stale value. Only call this when the host is known
+    /// not to be consuming the channel, for example right before a probe (re)connects.
+    pub fn clear(&mut self) {
+        self.channel().clear();
     }
 
     /// Returns true if the channel is empty.
@@ -289,55 +990,614 @@ impl UpChannel {
 
     /// Wait until all data has been read by the debugger.
     ///
+    /// This spins until the host has consumed everything in the buffer regardless of the channel
+    /// mode, which makes it suitable for shutdown and panic paths where all output must reach the
+    /// host before continuing.
+    ///
     /// *Note: This means that if no debugger is connected or if it isn't reading the rtt data,*
-    /// *this function will wait indefinitely.*
+    /// *this function will wait indefinitely. Use [`flush_with_timeout`] if that is a concern.*
+    ///
+    /// [`flush_with_timeout`]: UpChannel::flush_with_timeout
     pub fn flush(&self) {
         loop {
             if self.is_empty() {
                 break;
             }
-            core::hint::spin_loop();
+            rtt::block_wait();
         }
     }
-}
 
-impl fmt::Write for UpChannel {
-    fn write_str(&mut self, s: &str) -> Result<(), fmt::Error> {
-        self.channel().writer().write_str(s)
+    /// Same as [`flush`](UpChannel::flush), kept under this name for discoverability.
+    pub fn flush_blocking(&mut self) {
+        self.flush();
     }
 
-    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> Result<(), fmt::Error> {
-        self.channel().writer().write_fmt(args)
-    }
-}
+    /// Wait until all data has been read by the debugger if the channel is in `BlockIfFull` mode,
+    /// or return immediately in the non-blocking modes. In blocking mode the wait gives up after
+    /// the [block timeout](UpChannel::set_block_timeout) if one is set.
+    ///
+    /// Formatted writes with [`write!`] return as soon as the data is in the buffer. Call this
+    /// after a series of writes to make sure the output has been delivered before continuing, in
+    /// the same cases where a write would have waited for the host. Unlike
+    /// [`flush`](UpChannel::flush) this never hangs the application in a non-blocking mode.
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    ///
+    /// for value in values {
+    ///     writeln!(channel, "{}", value).ok();
+    /// }
+    /// channel.drain();
+    /// ```
+    pub fn drain(&mut self) {
+        if self.mode() != ChannelMode::BlockIfFull {
+            return;
+        }
 
-/// Writer for ufmt. Don't store an instance of this, but rather create a new one for every write.
-#[allow(non_camel_case_types)]
-pub struct uWriter<'c>(rtt::RttWriter<'c>);
+        match self.block_timeout {
+            Some(max_spins) => {
+                self.flush_with_timeout(max_spins);
+            }
+            None => self.flush(),
+        }
+    }
 
-impl uWrite for uWriter<'_> {
-    type Error = Infallible;
+    /// Wait until all data has been read by the debugger, but give up after `max_spins` polls of
+    /// the buffer. Returns true if the buffer was drained and false on timeout.
+    ///
+    /// As there is no portable clock, the timeout is expressed as a number of polling iterations.
+    /// The actual duration therefore depends on the CPU speed.
+    pub fn flush_with_timeout(&self, max_spins: u32) -> bool {
+        for _ in 0..max_spins {
+            if self.is_empty() {
+                return true;
+            }
+            rtt::block_wait();
+        }
 
-    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
-        self.0.write(s.as_bytes());
-        Ok(())
+        self.is_empty()
     }
-}
-
-/// RTT down (host to target) channel
-pub struct DownChannel(*mut rtt::RttChannel);
+
+    /// Wait until at least `needed` bytes can be written to the channel without blocking or
+    /// dropping data, as reported by [`writable_len`](UpChannel::writable_len). This allows
+    /// preparing a chunk of data knowing that it will fit in the buffer once it's done.
+    ///
+    /// *Note: This waits indefinitely if no debugger is reading the channel, or if `needed` is*
+    /// *larger than the buffer can ever hold. Use [`wait_for_space_with_timeout`] if that is a*
+    /// *concern.*
+    ///
+    /// [`wait_for_space_with_timeout`]: UpChannel::wait_for_space_with_timeout
+    pub fn wait_for_space(&mut self, needed: usize) {
+        while self.writable_len() < needed {
+            rtt::block_wait();
+        }
+    }
+
+    /// Wait until at least `needed` bytes can be written to the channel, but give up after
+    /// `max_spins` polls of the buffer. Returns true if there is enough space and false on timeout.
+    ///
+    /// As with [`flush_with_timeout`](UpChannel::flush_with_timeout), the actual duration of the
+    /// timeout depends on the CPU speed.
+    pub fn wait_for_space_with_timeout(&mut self, needed: usize, max_spins: u32) -> bool {
+        for _ in 0..max_spins {
+            if self.writable_len() >= needed {
+                return true;
+            }
+            rtt::block_wait();
+        }
+
+        self.writable_len() >= needed
+    }
+}
+
+impl fmt::Write for UpChannel {
+    fn write_str(&mut self, s: &str) -> Result<(), fmt::Error> {
+        self.writer().write_str(s)
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> Result<(), fmt::Error> {
+        self.writer().write_fmt(args)
+    }
+}
+
+/// Snapshot of the statistics of an up channel, returned by [`UpChannel::take_stats`].
+#[cfg(feature = "stats")]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ChannelStats {
+    /// See [`UpChannel::dropped_bytes`].
+    pub dropped_bytes: u64,
+
+    /// See [`UpChannel::total_written`].
+    pub total_written: u64,
+
+    /// See [`UpChannel::high_water_mark`].
+    pub high_water_mark: usize,
+}
+
+/// A sink for both binary data and formatted text, such as an [`UpChannel`].
+///
+/// The trait is object safe, so code that shouldn't depend on the concrete channel type can hold a
+/// `&mut dyn RttWrite` and use it for both kinds of output:
+///
+/// ```
+/// use core::fmt::Write;
+///
+/// fn report(out: &mut dyn RttWrite, raw: &[u8]) {
+///     writeln!(out, "{} bytes:", raw.len()).ok();
+///     out.write_bytes(raw);
+/// }
+/// ```
+pub trait RttWrite: fmt::Write {
+    /// Writes `buf` and returns the number of bytes written.
+    fn write_bytes(&mut self, buf: &[u8]) -> usize;
+}
+
+impl RttWrite for UpChannel {
+    /// Same as [`UpChannel::write`].
+    fn write_bytes(&mut self, buf: &[u8]) -> usize {
+        self.write(buf)
+    }
+}
+
+/// [`fmt::Write`] adapter for an [`UpChannel`] that reports data that couldn't be written as an
+/// error.
+///
+/// The [`fmt::Write`] implementation of `UpChannel` itself follows the channel mode and never
+/// returns an error, which suits logging that must never disturb the application. Use this
+/// wrapper instead when the caller needs to know whether everything reached the buffer: in
+/// `BlockIfFull` mode it blocks until all data has been written, and in the non-blocking modes each
+/// [`write_str`](fmt::Write::write_str) call fails with [`fmt::Error`] if its data didn't fit.
+/// Formatting stops at the first error, so the parts of the message before it may already have
+/// been written.
+///
+/// ```
+/// use core::fmt::Write;
+///
+/// writeln!(BlockingWriter(&mut channels.up.0), "Hello, world!")?;
+/// ```
+pub struct BlockingWriter<'c>(pub &'c mut UpChannel);
+
+impl fmt::Write for BlockingWriter<'_> {
+    fn write_str(&mut self, s: &str) -> Result<(), fmt::Error> {
+        self.0.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+/// Writer that forwards everything written to it to two inner writers, for example to send logs to
+/// both an RTT channel and a UART.
+///
+/// Writing is best effort: every write is passed to both writers, and errors from either one are
+/// ignored so that a failing writer doesn't keep the other one from receiving the rest of the
+/// output. The writes therefore always succeed.
+///
+/// Implements [`fmt::Write`] if both writers implement it, and [`uWrite`] if both writers implement
+/// that.
+///
+/// ```
+/// use core::fmt::Write;
+///
+/// let mut tee = TeeWriter(&mut channels.up.0, &mut uart);
+/// writeln!(tee, "Hello, world!").ok();
+/// ```
+pub struct TeeWriter<A, B>(pub A, pub B);
+
+impl<A: fmt::Write, B: fmt::Write> fmt::Write for TeeWriter<A, B> {
+    fn write_str(&mut self, s: &str) -> Result<(), fmt::Error> {
+        self.0.write_str(s).ok();
+        self.1.write_str(s).ok();
+        Ok(())
+    }
+}
+
+impl<A: uWrite, B: uWrite> uWrite for TeeWriter<A, B> {
+    type Error = Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.0.write_str(s).ok();
+        self.1.write_str(s).ok();
+        Ok(())
+    }
+}
+
+/// Wrapper around an up channel that drops messages which come too soon after the previous one,
+/// to keep a noisy source from flooding the host.
+///
+/// The crate doesn't know about any clock, so the current time is passed in with each message as
+/// a tick count from a source of the program's choosing, such as a hardware timer. A message is
+/// written if at least `min_interval` ticks have passed since the last message that was written.
+/// A message that the channel drops, for example because the buffer is full in a non-blocking mode,
+/// doesn't count as written, so the next message isn't held back by it. The tick counter may wrap
+/// around, as long as less than `u32::MAX` ticks pass between messages.
+///
+/// ```
+/// let mut limited = RateLimited::new(&mut channels.up.1, 1000);
+///
+/// loop {
+///     limited.write_fmt(timer.ticks(), format_args!("sensor: {}\n", read_sensor()));
+/// }
+/// ```
+pub struct RateLimited<'a> {
+    channel: &'a mut UpChannel,
+    min_interval: u32,
+    last: Option<u32>,
+}
+
+impl<'a> RateLimited<'a> {
+    /// Creates a wrapper that writes at most one message every `min_interval` ticks to `channel`.
+    pub fn new(channel: &'a mut UpChannel, min_interval: u32) -> Self {
+        RateLimited {
+            channel,
+            min_interval,
+            last: None,
+        }
+    }
+
+    /// Writes `s` to the channel if enough time has passed since the last message, and returns
+    /// true if any of it was written.
+    pub fn write_str(&mut self, now: u32, s: &str) -> bool {
+        if !self.is_due(now) {
+            return false;
+        }
+
+        let written = self.channel.write(s.as_bytes());
+        self.record(now, written)
+    }
+
+    /// Writes formatted data to the channel if enough time has passed since the last message, and
+    /// returns true if any of it was written. The arguments are not formatted if the message is
+    /// dropped for coming too soon.
+    pub fn write_fmt(&mut self, now: u32, args: fmt::Arguments<'_>) -> bool {
+        if !self.is_due(now) {
+            return false;
+        }
+
+        let mut writer = self.channel.writer();
+        fmt::Write::write_fmt(&mut writer, args).ok();
+        let written = writer.commit();
+        self.record(now, written)
+    }
+
+    fn is_due(&self, now: u32) -> bool {
+        match self.last {
+            Some(last) => now.wrapping_sub(last) >= self.min_interval,
+            None => true,
+        }
+    }
+
+    /// Starts a new interval at `now` if anything was written.
+    fn record(&mut self, now: u32, written: usize) -> bool {
+        if written == 0 {
+            return false;
+        }
+
+        self.last = Some(now);
+        true
+    }
+}
+
+/// Wrapper around an up channel that collects writes in a buffer of `N` bytes and passes them on to
+/// the channel in one piece.
+///
+/// Formatting with [`write!`] issues many small writes, each of which updates the ring buffer
+/// pointers. Collecting them first makes each message a single contiguous write of the channel,
+/// which is cheaper and can't be interleaved with other writers in the middle. The buffer is
+/// written to the channel when it's full, when [`flush`](BufferedUpChannel::flush) is called
+/// and when the wrapper is dropped. Data that is still in the buffer hasn't been delivered, so
+/// make sure to flush or drop the wrapper after a message is complete.
+///
+/// ```
+/// use core::fmt::Write;
+///
+/// let mut buffered = BufferedUpChannel::<64>::new(&mut channels.up.0);
+/// write!(buffered, "x={} y={}\n", x, y).ok();
+/// buffered.flush();
+/// ```
+pub struct BufferedUpChannel<'a, const N: usize> {
+    channel: &'a mut UpChannel,
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<'a, const N: usize> BufferedUpChannel<'a, N> {
+    /// Creates a wrapper with an empty buffer around `channel`.
+    pub fn new(channel: &'a mut UpChannel) -> Self {
+        BufferedUpChannel {
+            channel,
+            buf: [0; N],
+            len: 0,
+        }
+    }
+
+    /// Adds `buf` to the buffer, first writing the buffered data to the channel if there isn't
+    /// enough room. Data that is larger than the whole buffer is written to the channel directly.
+    pub fn write(&mut self, buf: &[u8]) {
+        if buf.len() > N - self.len {
+            self.flush();
+        }
+
+        if buf.len() > N {
+            self.channel.write(buf);
+            return;
+        }
+
+        self.buf[self.len..self.len + buf.len()].copy_from_slice(buf);
+        self.len += buf.len();
+    }
+
+    /// Writes the buffered data to the channel and returns the number of bytes written. Behavior
+    /// when the channel buffer is full is subject to the channel blocking mode. The buffer is empty
+    /// afterwards either way.
+    pub fn flush(&mut self) -> usize {
+        if self.len == 0 {
+            return 0;
+        }
+
+        let written = self.channel.write(&self.buf[..self.len]);
+        self.len = 0;
+
+        written
+    }
+}
+
+impl<const N: usize> fmt::Write for BufferedUpChannel<'_, N> {
+    fn write_str(&mut self, s: &str) -> Result<(), fmt::Error> {
+        self.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl<const N: usize> Drop for BufferedUpChannel<'_, N> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Temporarily sets the blocking mode of an up channel, created with [`UpChannel::mode_scope`].
+/// The previous mode is restored when the guard is dropped, including when a panic unwinds past it.
+///
+/// The guard borrows the channel mutably for its whole lifetime, so the channel can only be used
+/// through the guard, which dereferences to [`UpChannel`], until it is dropped. Other channel
+/// objects for the same buffer, for example ones created with [`conjure`](UpChannel::conjure), and
+/// the host can still see and change the mode while the guard exists.
+pub struct ModeGuard<'c> {
+    channel: &'c mut UpChannel,
+    previous: ChannelMode,
+}
+
+impl Deref for ModeGuard<'_> {
+    type Target = UpChannel;
+
+    fn deref(&self) -> &UpChannel {
+        self.channel
+    }
+}
+
+impl DerefMut for ModeGuard<'_> {
+    fn deref_mut(&mut self) -> &mut UpChannel {
+        self.channel
+    }
+}
+
+impl Drop for ModeGuard<'_> {
+    fn drop(&mut self) {
+        self.channel.set_mode(self.previous);
+    }
+}
+
+/// Writer for ufmt. Don't store an instance of this, but rather create a new one for every write.
+#[allow(non_camel_case_types)]
+pub struct uWriter<'c>(rtt::RttWriter<'c>);
+
+impl uWrite for uWriter<'_> {
+    type Error = Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.0.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Error returned when a channel doesn't have enough buffer space for a complete write and the
+/// channel mode doesn't allow waiting for more space.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct WouldBlockError;
+
+impl fmt::Display for WouldBlockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("not enough space in RTT buffer")
+    }
+}
+
+/// Error returned by [`DownChannel::read_line`] and [`DownChannel::read_until`] when a line doesn't
+/// fit in the buffer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LineTooLongError;
+
+impl fmt::Display for LineTooLongError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("line too long for buffer")
+    }
+}
+
+/// Error returned by [`UpChannelBuilder::build`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChannelBuildError {
+    /// RTT has not been initialized, or the control block has no up channel with the number.
+    NoSuchChannel,
+
+    /// The channel already has a buffer.
+    InUse,
+
+    /// The buffer is shorter than two bytes. One byte of the buffer always stays empty, so a
+    /// smaller buffer can't hold any data.
+    BufferTooSmall,
+}
+
+impl fmt::Display for ChannelBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ChannelBuildError::NoSuchChannel => "no such RTT channel",
+            ChannelBuildError::InUse => "RTT channel already in use",
+            ChannelBuildError::BufferTooSmall => "RTT channel buffer too small",
+        })
+    }
+}
+
+/// Sets up an up channel at runtime with a buffer, name and mode chosen by the program, for example
+/// by a board support crate that places the buffer in a memory region only known at runtime.
+///
+/// The control block still has to be created with `rtt_init`, with a slot for the channel that is
+/// declared as `empty`. Such a channel has no buffer and is not shown to the host until one is
+/// set with this builder. The channel object that `rtt_init` returns for it should be discarded,
+/// and writes to it do nothing.
+///
+/// ```
+/// let _ = rtt_init! { up: { 0: { size: 1024 } 1: { empty } } };
+///
+/// let log = unsafe {
+///     UpChannelBuilder::new(1, board::rtt_buffer())
+///         .name(CStr::from_bytes_with_nul(b"Log\0").unwrap())
+///         .mode(ChannelMode::NoBlockTrim)
+///         .build()
+/// }
+/// .unwrap();
+/// ```
+pub struct UpChannelBuilder {
+    number: usize,
+    name: Option<&'static CStr>,
+    mode: ChannelMode,
+    skip_per_write: bool,
+    buffer: &'static mut [u8],
+}
+
+impl UpChannelBuilder {
+    /// Creates a builder for up channel `number` that uses `buffer`. The channel has no name and
+    /// is in `NoBlockSkip` mode unless set otherwise.
+    pub fn new(number: usize, buffer: &'static mut [u8]) -> Self {
+        UpChannelBuilder {
+            number,
+            name: None,
+            mode: ChannelMode::NoBlockSkip,
+            skip_per_write: false,
+            buffer,
+        }
+    }
+
+    /// Sets the name of the channel.
+    pub fn name(mut self, name: &'static CStr) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Sets the initial blocking mode of the channel.
+    pub fn mode(mut self, mode: ChannelMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Skips each write separately in `NoBlockSkip` mode, see [`UpChannel::set_skip_per_write`].
+    pub fn skip_per_write(mut self, skip_per_write: bool) -> Self {
+        self.skip_per_write = skip_per_write;
+        self
+    }
+
+    /// Registers the buffer in the control block and returns the channel. Fails if the control
+    /// block has no such channel, if the channel already has a buffer, or if the buffer is too
+    /// small.
+    ///
+    /// Calling this function will cause a linking error if `rtt_init` has not been called.
+    ///
+    /// # Safety
+    ///
+    /// This function must only be called after `rtt_init` has been called. It's undefined behavior
+    /// to call it for the same channel from multiple contexts at once.
+    pub unsafe fn build(self) -> Result<UpChannel, ChannelBuildError> {
+        if self.buffer.len() < 2 {
+            return Err(ChannelBuildError::BufferTooSmall);
+        }
+
+        let ptr = rtt::channel_ptr(rtt::Direction::Up, self.number)
+            .ok_or(ChannelBuildError::NoSuchChannel)?;
+
+        if (*ptr).is_initialized() {
+            return Err(ChannelBuildError::InUse);
+        }
+
+        (*ptr).replace_buffer(self.name, self.mode, self.buffer);
+
+        let mut channel = UpChannel::new(ptr, self.number);
+        channel.set_skip_per_write(self.skip_per_write);
+
+        Ok(channel)
+    }
+}
+
+/// RTT down (host to target) channel
+pub struct DownChannel {
+    channel: *mut rtt::RttChannel,
+    number: usize,
+    line_len: usize,
+    stray_byte: Option<u8>,
+}
 
 unsafe impl Send for DownChannel {}
 
 impl DownChannel {
     /// Public due to access from macro.
     #[doc(hidden)]
-    pub unsafe fn new(channel: *mut rtt::RttChannel) -> Self {
-        DownChannel(channel)
+    pub unsafe fn new(channel: *mut rtt::RttChannel, number: usize) -> Self {
+        DownChannel {
+            channel,
+            number,
+            line_len: 0,
+            stray_byte: None,
+        }
     }
 
     fn channel(&mut self) -> &mut rtt::RttChannel {
-        unsafe { &mut *self.0 }
+        unsafe { &mut *self.channel }
+    }
+
+    /// Returns the number of the channel.
+    pub fn number(&self) -> usize {
+        self.number
+    }
+
+    /// Returns the name of the channel as stored in the control block, or `None` if the channel
+    /// has no name or the name is not valid UTF-8.
+    pub fn name(&self) -> Option<&str> {
+        unsafe { &*self.channel }.name()
+    }
+
+    /// Returns the size of the channel buffer in bytes. See [`UpChannel::buffer_len`].
+    pub fn buffer_len(&self) -> usize {
+        unsafe { &*self.channel }.size()
+    }
+
+    /// Creates a down channel that uses `buf` as its buffer instead of the one reserved by
+    /// `rtt_init`. Returns `None` under the same conditions as [`UpChannel::from_static_buffer`],
+    /// including when `buf` is shorter than 2 bytes. See there for details.
+    ///
+    /// # Safety
+    ///
+    /// The same requirements as for [`UpChannel::from_static_buffer`] apply.
+    pub unsafe fn from_static_buffer(
+        number: usize,
+        name: Option<&'static CStr>,
+        buf: &'static mut [u8],
+    ) -> Option<DownChannel> {
+        if buf.len() < 2 {
+            return None;
+        }
+
+        let ptr = rtt::channel_ptr(rtt::Direction::Down, number)?;
+        if !(*ptr).is_initialized() {
+            return None;
+        }
+
+        (*ptr).replace_buffer(name, (*ptr).mode(), buf);
+
+        Some(DownChannel::new(ptr, number))
     }
 
     /// Reads up to `buf.len()` bytes from the channel and return the number of bytes read. This
@@ -345,10 +1605,246 @@ impl DownChannel {
     pub fn read(&mut self, buf: &mut [u8]) -> usize {
         self.channel().read(buf)
     }
+
+    /// Returns the number of bytes that are available for reading. Zero means the buffer was
+    /// empty at the time of the call.
+    pub fn available(&mut self) -> usize {
+        self.channel().readable_len()
+    }
+
+    /// Copies up to `buf.len()` bytes from the channel without consuming them and returns the
+    /// number of bytes copied. A subsequent [`read`](DownChannel::read) will return the same data.
+    /// This method never blocks.
+    pub fn peek(&mut self, buf: &mut [u8]) -> usize {
+        self.channel().peek(buf)
+    }
+
+    /// Passes all data that is available for reading to `f` directly from the channel buffer and
+    /// returns the number of bytes consumed. This method never blocks.
+    ///
+    /// Because the buffer is a ring buffer, the data may be split in two at the wrap point, so `f`
+    /// is called zero, one or two times. The data is consumed once `f` has seen all of it.
+    ///
+    /// ```
+    /// channels.down.0.poll_into(|data| parser.feed(data));
+    /// ```
+    pub fn poll_into<F: FnMut(&[u8])>(&mut self, f: F) -> usize {
+        self.channel().read_with(f)
+    }
+
+    /// Passes the available bytes to `f` one at a time until `f` returns false or there is no more
+    /// data, and returns the number of bytes consumed. This method never blocks.
+    ///
+    /// A byte for which `f` returns false is not consumed and will be the first byte of the next
+    /// read. This allows filling a container of your own directly from the channel buffer, stopping
+    /// when it's full:
+    ///
+    /// ```
+    /// channels.down.0.read_extend(|byte| queue.push(byte).is_ok());
+    /// ```
+    pub fn read_extend<F: FnMut(u8) -> bool>(&mut self, f: F) -> usize {
+        self.channel().read_while(f)
+    }
+
+    /// Appends the available bytes to `vec` until there is no more data or `vec` is full, and
+    /// returns the number of bytes appended. This method never blocks. Data that doesn't fit in
+    /// `vec` is left in the channel for the next read. This requires the `heapless` feature.
+    #[cfg(feature = "heapless")]
+    pub fn read_to_vec<const N: usize>(&mut self, vec: &mut heapless::Vec<u8, N>) -> usize {
+        self.read_extend(|byte| vec.push(byte).is_ok())
+    }
+
+    /// Reads a newline terminated line into `buf`, accumulating data across calls. This method
+    /// never blocks.
+    ///
+    /// Returns `Ok(Some(len))` once a complete line has been read, where `len` is the length of the
+    /// line in `buf` excluding the newline, or `Ok(None)` if no complete line is available yet. The
+    /// partial line is kept in `buf`, so the same buffer has to be passed to every call until a
+    /// line is complete. Data after the newline is left in the channel for the next line.
+    ///
+    /// Returns `Err` if `buf` fills up without a newline. The partial line is then discarded, and
+    /// the next call starts a new line with whatever data follows.
+    ///
+    /// ```
+    /// let mut line = [0u8; 64];
+    /// loop {
+    ///     if let Ok(Some(len)) = channels.down.0.read_line(&mut line) {
+    ///         handle_command(&line[..len]);
+    ///     }
+    /// }
+    /// ```
+    pub fn read_line(&mut self, buf: &mut [u8]) -> Result<Option<usize>, LineTooLongError> {
+        self.read_until(b'\n', buf)
+    }
+
+    /// Reads data terminated by `delim` into `buf`, accumulating data across calls. This works
+    /// exactly like [`read_line`](DownChannel::read_line), except that the data ends at `delim`
+    /// instead of a newline, for example a zero byte that separates records.
+    ///
+    /// The partial data is tracked in the channel object, so `read_until` and `read_line` must not
+    /// be mixed while data is partially read.
+    pub fn read_until(
+        &mut self,
+        delim: u8,
+        buf: &mut [u8],
+    ) -> Result<Option<usize>, LineTooLongError> {
+        let start = min(self.line_len, buf.len());
+        let count = self.peek(&mut buf[start..]);
+
+        match buf[start..start + count].iter().position(|&b| b == delim) {
+            Some(pos) => {
+                // Consume the data including the delimiter, but nothing after it
+                self.read(&mut buf[start..start + pos + 1]);
+                self.line_len = 0;
+
+                Ok(Some(start + pos))
+            }
+            None => {
+                self.read(&mut buf[start..start + count]);
+                self.line_len = start + count;
+
+                if self.line_len == buf.len() {
+                    self.line_len = 0;
+                    return Err(LineTooLongError);
+                }
+
+                Ok(None)
+            }
+        }
+    }
+
+    /// Reads a line of UTF-16LE text into `buf`, accumulating data across calls like
+    /// [`read_line`](DownChannel::read_line). The bytes are assembled into `u16` code units, and a
+    /// line ends with a code unit with the value of `'\n'`. The result is the same as for
+    /// `read_line`, counted in code units instead of bytes.
+    ///
+    /// If only the first byte of a code unit has arrived, it's kept in the channel object until the
+    /// second one is read. The code units are not checked to be valid UTF-16, so pairs of
+    /// surrogates are returned as they are, for example for [`char::decode_utf16`]. As with
+    /// `read_line`, the state is tracked in the channel object, so the line reading methods must not
+    /// be mixed while a line is partially read.
+    pub fn read_utf16_line(&mut self, buf: &mut [u16]) -> Result<Option<usize>, LineTooLongError> {
+        if buf.is_empty() {
+            return Err(LineTooLongError);
+        }
+
+        // A partial line from a call with a longer buffer may not fit in this one
+        self.line_len = min(self.line_len, buf.len());
+        if self.line_len == buf.len() {
+            self.line_len = 0;
+            return Err(LineTooLongError);
+        }
+
+        let mut chunk = [0u8; 64];
+
+        loop {
+            let count = self.peek(&mut chunk);
+            if count == 0 {
+                return Ok(None);
+            }
+
+            for used in 1..=count {
+                let byte = chunk[used - 1];
+
+                let low = match self.stray_byte.take() {
+                    Some(low) => low,
+                    None => {
+                        self.stray_byte = Some(byte);
+                        continue;
+                    }
+                };
+
+                let unit = u16::from_le_bytes([low, byte]);
+                buf[self.line_len] = unit;
+                self.line_len += 1;
+
+                if unit == u16::from(b'\n') {
+                    // Consume the data including the terminator, but nothing after it
+                    self.read(&mut chunk[..used]);
+                    let len = self.line_len - 1;
+                    self.line_len = 0;
+
+                    return Ok(Some(len));
+                }
+
+                if self.line_len == buf.len() {
+                    self.read(&mut chunk[..used]);
+                    self.line_len = 0;
+
+                    return Err(LineTooLongError);
+                }
+            }
+
+            self.read(&mut chunk[..count]);
+        }
+    }
+
+    /// Returns an iterator over the bytes that are available for reading. The iterator ends when
+    /// the buffer is empty and never blocks.
+    ///
+    /// Each byte is consumed from the channel as the iterator yields it, so bytes that were not
+    /// iterated over are left in the channel for the next read.
+    ///
+    /// ```
+    /// for byte in channels.down.0.bytes() {
+    ///     parser.feed(byte);
+    /// }
+    /// ```
+    pub fn bytes(&mut self) -> impl Iterator<Item = u8> + '_ {
+        core::iter::from_fn(move || {
+            let mut byte = [0u8];
+            if self.read(&mut byte) == 1 {
+                Some(byte[0])
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Reads exactly `buf.len()` bytes from the channel, polling the buffer until enough data has
+    /// arrived. Gives up after `max_spins` polls that didn't complete the read and returns
+    /// `Err` with the number of bytes that were read into `buf` so far.
+    ///
+    /// Passing 0 for `max_spins` makes this non-blocking: it succeeds only if all the data is
+    /// already available. Bytes read before giving up are consumed from the channel either way.
+    pub fn read_exact(&mut self, buf: &mut [u8], max_spins: u32) -> Result<(), usize> {
+        let mut total = self.read(buf);
+
+        for _ in 0..max_spins {
+            if total == buf.len() {
+                break;
+            }
+            rtt::block_wait();
+            total += self.read(&mut buf[total..]);
+        }
+
+        if total == buf.len() {
+            Ok(())
+        } else {
+            Err(total)
+        }
+    }
+}
+
+/// Returns the index of the first channel in `channels` that has data available for reading, or
+/// `None` if they are all empty. This never blocks and only reads the buffer pointers of each
+/// channel, so it's cheap to call from a main loop to decide which channel to handle next.
+///
+/// ```
+/// let mut channels = [&mut commands, &mut config, &mut firmware];
+///
+/// if let Some(index) = poll_down(&mut channels) {
+///     handle_input(index, &mut *channels[index]);
+/// }
+/// ```
+pub fn poll_down(channels: &mut [&mut DownChannel]) -> Option<usize> {
+    channels
+        .iter_mut()
+        .position(|channel| channel.available() > 0)
 }
 
 /// Specifies what to do when a channel doesn't have enough buffer space for a complete write.
-#[derive(Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 #[repr(usize)]
 pub enum ChannelMode {
     /// Skip writing the data completely if it doesn't fit in its entirety.
@@ -359,10 +1855,26 @@ pub enum ChannelMode {
 
     /// Block (spin) if the buffer is full. If within a critical section such as inside
     /// [`rprintln`], this will cause the application to freeze until the host reads from the
-    /// buffer.
+    /// buffer. The wait can be bounded with [`UpChannel::set_block_timeout`].
     BlockIfFull = 2,
 }
 
+impl TryFrom<usize> for ChannelMode {
+    type Error = usize;
+
+    /// Converts a mode value as stored in the control block back into a `ChannelMode`. Returns the
+    /// value itself as the error if it is not in `0..=2`. The value 3 is reserved by the RTT
+    /// protocol.
+    fn try_from(value: usize) -> Result<Self, usize> {
+        match value {
+            0 => Ok(ChannelMode::NoBlockSkip),
+            1 => Ok(ChannelMode::NoBlockTrim),
+            2 => Ok(ChannelMode::BlockIfFull),
+            _ => Err(value),
+        }
+    }
+}
+
 /// An up channel that supports writing into multiple virtual terminals within the same buffer.
 ///
 /// An [`UpChannel`] can be turned into a `TerminalChannel` by using the
@@ -394,19 +1906,14 @@ impl TerminalChannel {
     /// The writer supports formatted writing with the standard [`Write`] and [`ufmt_write::uWrite`].
     ///
     /// [`Write`]: fmt::Write
-    pub fn write(&mut self, number: u8) -> TerminalWriter {
+    pub fn write(&mut self, number: u8) -> TerminalWriter<'_> {
         const TERMINAL_ID: [u8; 16] = *b"0123456789ABCDEF";
 
-        let mut writer = self.channel.channel().writer();
+        let mut writer = self.channel.writer();
 
         if number != self.current {
             // The terminal switch command must be sent in full so the mode cannot be NoBlockTrim
-            let mode = self.channel.mode();
-            let mode = if mode == ChannelMode::NoBlockTrim {
-                ChannelMode::NoBlockSkip
-            } else {
-                mode
-            };
+            let mode = self.channel.frame_mode(2);
 
             writer.write_with_mode(mode, &[0xff, TERMINAL_ID[(number & 0x0f) as usize]]);
 
@@ -430,6 +1937,17 @@ impl TerminalChannel {
         self.channel.set_mode(mode)
     }
 
+    /// Gets the block timeout of the channel. See [`UpChannel::block_timeout`].
+    pub fn block_timeout(&self) -> Option<u32> {
+        self.channel.block_timeout()
+    }
+
+    /// Limits how long a write waits for buffer space in `BlockIfFull` mode. See
+    /// [`UpChannel::set_block_timeout`] for details.
+    pub fn set_block_timeout(&mut self, max_spins: Option<u32>) {
+        self.channel.set_block_timeout(max_spins)
+    }
+
     /// Returns true if the channel is empty.
     pub fn is_empty(&self) -> bool {
         self.channel.is_empty()
@@ -442,6 +1960,12 @@ impl TerminalChannel {
     pub fn flush(&self) {
         self.channel.flush();
     }
+
+    /// Wait until all data has been read by the debugger, but give up after `max_spins` polls of
+    /// the buffer. See [`UpChannel::flush_with_timeout`] for details.
+    pub fn flush_with_timeout(&self, max_spins: u32) -> bool {
+        self.channel.flush_with_timeout(max_spins)
+    }
 }
 
 /// Formatted writing operation. Don't store an instance of this, but rather create a new one for
@@ -482,3 +2006,119 @@ impl Drop for TerminalWriter<'_> {
 pub mod export {
     pub use critical_section;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtt::tests::{channel, read_all};
+
+    #[test]
+    fn frame_is_dropped_when_block_timeout_expires() {
+        let chan = channel(16, ChannelMode::BlockIfFull);
+        let mut up = unsafe { UpChannel::new(chan, 0) };
+        up.set_block_timeout(Some(10));
+
+        up.write(&[0xaa; 10]);
+        assert_eq!(up.write_frame(&[1, 2, 3, 4]), Err(WouldBlockError));
+        assert_eq!(read_all(chan), [0xaa; 10]);
+
+        assert_eq!(up.write_frame(&[1, 2, 3, 4]), Ok(8));
+        assert_eq!(read_all(chan), [4, 0, 0, 0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn skip_per_write_keeps_parts_that_fit() {
+        let chan = channel(16, ChannelMode::NoBlockSkip);
+        let mut up = unsafe { UpChannel::new(chan, 0) };
+
+        up.set_skip_per_write(true);
+        assert_eq!(up.mode(), ChannelMode::NoBlockSkip);
+
+        // Each argument is a separate part, unless the compiler inlines it into the format string
+        let (first, second) = (1234567890, 123456);
+        fmt::Write::write_fmt(&mut up, format_args!("{}{}", first, second)).ok();
+        assert_eq!(read_all(chan), b"1234567890");
+
+        // Without it the whole write is skipped
+        up.set_skip_per_write(false);
+        fmt::Write::write_fmt(&mut up, format_args!("{}{}", first, second)).ok();
+        assert_eq!(read_all(chan), b"");
+    }
+
+    #[test]
+    fn mode_try_from_rejects_values_outside_protocol_modes() {
+        for mode in [
+            ChannelMode::NoBlockSkip,
+            ChannelMode::NoBlockTrim,
+            ChannelMode::BlockIfFull,
+        ] {
+            assert_eq!(ChannelMode::try_from(mode as usize), Ok(mode));
+        }
+
+        assert_eq!(ChannelMode::try_from(3), Err(3));
+        assert_eq!(ChannelMode::try_from(4), Err(4));
+    }
+
+    #[test]
+    fn flush_blocking_returns_once_host_has_read_everything() {
+        let chan = channel(16, ChannelMode::NoBlockSkip);
+        let mut up = unsafe { UpChannel::new(chan, 0) };
+
+        up.write(b"pending");
+        assert!(!up.is_empty());
+
+        assert_eq!(read_all(chan), b"pending");
+        up.flush_blocking();
+        assert!(up.is_empty());
+    }
+
+    #[test]
+    fn rate_limited_ignores_dropped_messages() {
+        let chan = channel(16, ChannelMode::NoBlockSkip);
+        let mut up = unsafe { UpChannel::new(chan, 0) };
+        let mut limited = RateLimited::new(&mut up, 10);
+
+        assert!(limited.write_str(0, "first"));
+        assert!(!limited.write_str(5, "early"));
+
+        // Doesn't fit next to the first message, so it's skipped and the interval doesn't restart
+        assert!(!limited.write_str(10, "far too long"));
+        assert_eq!(read_all(chan), b"first");
+
+        let (a, b) = (1, 2);
+        assert!(limited.write_fmt(11, format_args!("{}{}", a, b)));
+        assert!(!limited.write_fmt(12, format_args!("{}{}", a, b)));
+        assert!(read_all(chan).ends_with(b"12"));
+    }
+
+    #[test]
+    #[cfg(not(feature = "disable-printing"))]
+    fn error_print_channel_is_not_selectable_by_index() {
+        let chan = channel(64, ChannelMode::NoBlockSkip);
+        set_error_print_channel(unsafe { UpChannel::new(chan, 0) });
+
+        rprint!(@ MAX_PRINT_CHANNELS, "by index");
+        rhexdump!(@ MAX_PRINT_CHANNELS, &[1, 2, 3]);
+        assert_eq!(read_all(chan), b"");
+
+        reprint!("error");
+        assert_eq!(read_all(chan), b"error");
+    }
+
+    #[test]
+    fn atomic_channel_reports_collisions() {
+        let chan = channel(64, ChannelMode::NoBlockSkip);
+        let shared = AtomicUpChannel::new(unsafe { UpChannel::new(chan, 0) });
+
+        // A write from a context that interrupts another one
+        let nested = shared.try_with(|channel| {
+            channel.write(b"outer ");
+            shared.write(b"inner")
+        });
+        assert_eq!(nested, Some(None));
+        assert_eq!(shared.collisions(), 1);
+
+        assert_eq!(shared.write(b"next"), Some(4));
+        assert_eq!(read_all(chan), b"outer next");
+    }
+}
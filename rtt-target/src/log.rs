@@ -1,6 +1,10 @@
-use crate::rprintln;
+use crate::{rprintln, with_terminal_channel, ChannelMode};
 use once_cell::sync::OnceCell;
 
+/// Number of polls [`log::Log::flush`] waits for the host if the print channel has no block
+/// timeout.
+const FLUSH_SPINS: u32 = 1_000_000;
+
 struct Logger {
     level_filter: log::LevelFilter,
 }
@@ -24,8 +28,17 @@ impl log::Log for Logger {
     }
 
     /// Flush buffered records.
+    ///
+    /// If the print channel is in `BlockIfFull` mode, this waits until the host has read all
+    /// pending output, but at most for the block timeout of the channel, or for `FLUSH_SPINS`
+    /// polls if it has none, so that it can't hang without a debugger attached. In the
+    /// non-blocking modes there is nothing to do.
     fn flush(&self) {
-        // Nothing to do here
+        with_terminal_channel(|term| {
+            if term.mode() == ChannelMode::BlockIfFull {
+                term.flush_with_timeout(term.block_timeout().unwrap_or(FLUSH_SPINS));
+            }
+        });
     }
 }
 
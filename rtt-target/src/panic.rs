@@ -0,0 +1,89 @@
+use core::cell::RefCell;
+use critical_section::Mutex;
+
+use crate::{ChannelMode, UpChannel};
+
+static PANIC_CHANNEL: Mutex<RefCell<Option<UpChannel>>> = Mutex::new(RefCell::new(None));
+
+/// Sets a dedicated up channel for panic messages, separate from the channels used for normal
+/// logging. See [`with_panic_channel`] for how the channel is written to.
+///
+/// The channel is switched to `BlockIfFull` mode right away, so that the host can see the mode
+/// before anything is written.
+pub fn set_panic_channel(mut channel: UpChannel) {
+    channel.set_mode(ChannelMode::BlockIfFull);
+
+    critical_section::with(|cs| *PANIC_CHANNEL.borrow_ref_mut(cs) = Some(channel));
+}
+
+/// Allows accessing the panic channel set with [`set_panic_channel`]. Returns false and does
+/// nothing if the channel hasn't been set.
+///
+/// Panic messages must never be dropped, so before calling `f` the channel is set to
+/// `BlockIfFull` mode without a block timeout, in case the host or other code has changed them.
+/// After `f` returns the channel is flushed, so everything has reached the host once this function
+/// returns. This means that it waits indefinitely if no debugger is reading the channel.
+///
+/// `panic-rtt-target` writes to the panic channel if it is set. A custom panic handler can use it
+/// as follows:
+///
+/// ```
+/// #[panic_handler]
+/// fn panic(info: &core::panic::PanicInfo) -> ! {
+///     with_panic_channel(|channel| {
+///         writeln!(channel, "{}", info).ok();
+///     });
+///
+///     loop {}
+/// }
+/// ```
+pub fn with_panic_channel<F: FnOnce(&mut UpChannel)>(f: F) -> bool {
+    critical_section::with(|cs| match &mut *PANIC_CHANNEL.borrow_ref_mut(cs) {
+        Some(channel) => {
+            channel.set_mode(ChannelMode::BlockIfFull);
+            channel.set_block_timeout(None);
+
+            f(channel);
+
+            channel.flush();
+            true
+        }
+        None => false,
+    })
+}
+
+/// Prints the panic message to the print channel, the panic channel and the defmt channel, if
+/// they are set, and halts. Enabled with the `panic-handler` feature.
+///
+/// The print channel is switched to `BlockIfFull` mode and flushed after the message is written,
+/// so the whole message reaches the host before the program halts. Like `panic-rtt-target`, this
+/// never leaves the critical section, so nothing else runs after a panic.
+///
+/// The handler is only defined for bare-metal targets, because the standard library brings its own
+/// handler on the others, such as when running tests on the host.
+#[cfg(all(feature = "panic-handler", target_os = "none"))]
+#[inline(never)]
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    use core::fmt::Write;
+    use portable_atomic::{compiler_fence, Ordering};
+
+    critical_section::with(|_| {
+        #[cfg(feature = "defmt")]
+        ::defmt::error!("{}", ::defmt::Display2Format(info));
+
+        crate::with_terminal_channel(|term| {
+            term.set_mode(ChannelMode::BlockIfFull);
+            writeln!(term.write(0), "{}", info).ok();
+            term.flush();
+        });
+
+        with_panic_channel(|channel| {
+            writeln!(channel, "{}", info).ok();
+        });
+
+        loop {
+            compiler_fence(Ordering::SeqCst);
+        }
+    })
+}
@@ -0,0 +1,42 @@
+use crate::UpChannel;
+use serde::Serialize;
+
+/// Size of the stack buffer used by [`UpChannel::write_postcard`].
+pub const POSTCARD_BUFFER_SIZE: usize = 128;
+
+impl UpChannel {
+    /// Serializes `value` with `postcard` and writes it to the channel as a single frame. Returns
+    /// the number of bytes written, which is zero if the message was dropped.
+    ///
+    /// The message is serialized into a stack buffer of [`POSTCARD_BUFFER_SIZE`] bytes first, so
+    /// that it is either written in full or not at all. Because messages are never split, the host
+    /// side can decode consecutive messages from the channel data with `postcard::take_from_bytes`.
+    /// In `NoBlockTrim` mode messages that don't fit are dropped instead of trimmed. In
+    /// `BlockIfFull` mode the write blocks until the whole message has been written, or with a
+    /// [block timeout](UpChannel::set_block_timeout), drops the message if there is no room for
+    /// all of it when the timeout expires, like [`write_frame`](UpChannel::write_frame).
+    ///
+    /// If the serialized message is larger than the buffer,
+    /// [`postcard::Error::SerializeBufferFull`] is returned and nothing is written. Use
+    /// [`write_postcard_with_buffer`](UpChannel::write_postcard_with_buffer) to supply a larger
+    /// buffer for such messages.
+    pub fn write_postcard<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<usize, postcard::Error> {
+        let mut buf = [0u8; POSTCARD_BUFFER_SIZE];
+        self.write_postcard_with_buffer(value, &mut buf)
+    }
+
+    /// Like [`write_postcard`](UpChannel::write_postcard) but serializes `value` into the
+    /// caller-supplied `buf`, which must be large enough for the serialized message.
+    pub fn write_postcard_with_buffer<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+        buf: &mut [u8],
+    ) -> Result<usize, postcard::Error> {
+        let data = postcard::to_slice(value, buf)?;
+
+        Ok(self.write_frame_parts(&[data]).unwrap_or(0))
+    }
+}
@@ -1,58 +1,453 @@
-use core::cell::RefCell;
+use core::cell::{Cell, RefCell};
 use core::fmt::{self, Write as _};
-use critical_section::Mutex;
+use critical_section::{CriticalSection, Mutex};
+use portable_atomic::{AtomicU8, Ordering};
 
-use crate::{TerminalChannel, TerminalWriter, UpChannel};
+use crate::rtt::RttChannel;
+use crate::{ChannelMode, TerminalChannel, TerminalWriter, UpChannel};
 
-static PRINT_TERMINAL: Mutex<RefCell<Option<TerminalChannel>>> = Mutex::new(RefCell::new(None));
+/// The number of print channels that can be set with [`set_print_channel_at`].
+pub const MAX_PRINT_CHANNELS: usize = 4;
+
+type PrintTerminal = Mutex<RefCell<Option<TerminalChannel>>>;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_TERMINAL: PrintTerminal = Mutex::new(RefCell::new(None));
+
+/// The error print channel is stored after the normal print channels. Its index is not accepted
+/// by the printing functions, which select it with [`print_impl::ERROR_CHANNEL`] instead, so that
+/// `rprint!(@ 4, ...)` can't reach it.
+const ERROR_PRINT_CHANNEL: usize = MAX_PRINT_CHANNELS;
+
+static PRINT_TERMINALS: [PrintTerminal; MAX_PRINT_CHANNELS + 1] =
+    [NO_TERMINAL; MAX_PRINT_CHANNELS + 1];
+
+type TimestampFn = fn() -> u64;
+
+static TIMESTAMP: Mutex<Cell<Option<TimestampFn>>> = Mutex::new(Cell::new(None));
+
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Trace as u8);
+
+/// Verbosity levels for [`rlog`], from the most to the least important.
+///
+/// [`rlog`]: crate::rlog
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[repr(u8)]
+pub enum LogLevel {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
 
 /// Sets the channel to use for [`rprint`], [`rprintln`], [`debug_rprint`] and [`debug_rprintln`].
 ///
+/// This is the same as calling [`set_print_channel_at`] with index 0.
+///
 /// [`rprint`]: crate::rprint
 /// [`rprintln`]: crate::rprintln
 /// [`debug_rprint`]: crate::debug_rprint
 /// [`debug_rprintln`]: crate::debug_rprintln
 pub fn set_print_channel(channel: UpChannel) {
+    set_print_channel_at(0, channel);
+}
+
+/// Sets the print channel with the given index, which can then be selected in the printing macros
+/// with the `@` syntax, e.g. `rprintln!(@ 1, "Hello!")`. This allows keeping the output of
+/// unrelated parts of an application in separate channels. Each print channel is locked
+/// independently.
+///
+/// # Panics
+///
+/// Panics if `index` is not less than [`MAX_PRINT_CHANNELS`].
+pub fn set_print_channel_at(index: usize, channel: UpChannel) {
+    assert!(
+        index < MAX_PRINT_CHANNELS,
+        "print channel index out of range"
+    );
+
+    critical_section::with(|cs| {
+        *PRINT_TERMINALS[index].borrow_ref_mut(cs) = Some(TerminalChannel::new(channel))
+    });
+}
+
+/// Returns true if the print channel has been set with [`rtt_init_print`] or [`set_print_channel`].
+///
+/// Printing before that is safe: the printing macros check for the channel and silently discard
+/// the message if it isn't set. This can be used to fall back to some other output during early
+/// boot, or to avoid formatting messages that would be discarded anyway.
+///
+/// [`rtt_init_print`]: crate::rtt_init_print
+pub fn is_print_initialized() -> bool {
+    critical_section::with(|cs| PRINT_TERMINALS[0].borrow_ref(cs).is_some())
+}
+
+/// Sets the channel to use for [`reprint`] and [`reprintln`], which are meant for errors and
+/// warnings like the standard error stream. Keeping them in a separate up channel from the normal
+/// output lets host tools show or store them separately.
+///
+/// [`reprint`]: crate::reprint
+/// [`reprintln`]: crate::reprintln
+pub fn set_error_print_channel(channel: UpChannel) {
     critical_section::with(|cs| {
-        *PRINT_TERMINAL.borrow_ref_mut(cs) = Some(TerminalChannel::new(UpChannel(channel.0)))
+        *PRINT_TERMINALS[ERROR_PRINT_CHANNEL].borrow_ref_mut(cs) =
+            Some(TerminalChannel::new(channel))
     });
 }
 
 /// Allows accessing the currently set print channel.
 pub fn with_terminal_channel<F: Fn(&mut TerminalChannel)>(f: F) {
-    critical_section::with(|cs| {
-        if let Some(term) = &mut *PRINT_TERMINAL.borrow_ref_mut(cs) {
-            f(term)
+    with_terminal_channel_at(0, f);
+}
+
+/// Allows accessing the print channel with the given index. Does nothing if the channel hasn't
+/// been set or the index is out of range.
+pub fn with_terminal_channel_at<F: Fn(&mut TerminalChannel)>(index: usize, f: F) {
+    if let Some(terminal) = PRINT_TERMINALS[..MAX_PRINT_CHANNELS].get(index) {
+        critical_section::with(|cs| {
+            if let Some(term) = &mut *terminal.borrow_ref_mut(cs) {
+                f(term)
+            }
+        });
+    }
+}
+
+/// Sets a function that returns the current time in arbitrary ticks, which [`rprintln`] will then
+/// prefix every line with, e.g. `[1234] Hello!`. Pass `None` to disable the prefix again, which is
+/// also the default.
+///
+/// The function is called from within the critical section used for printing, so it should be
+/// quick and must not print itself.
+///
+/// [`rprintln`]: crate::rprintln
+pub fn set_timestamp_fn(f: Option<TimestampFn>) {
+    critical_section::with(|cs| TIMESTAMP.borrow(cs).set(f));
+}
+
+/// Sets the least important level that [`rlog`] prints. Messages of less important levels are
+/// discarded before they are formatted. The default is [`LogLevel::Trace`], i.e. everything is
+/// printed.
+///
+/// This can be called at any time, for example from a command handler reading a down channel, to
+/// change the verbosity without recompiling.
+///
+/// [`rlog`]: crate::rlog
+pub fn set_log_level(level: LogLevel) {
+    LOG_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Returns true if [`rlog`] currently prints messages of the given level.
+///
+/// [`rlog`]: crate::rlog
+pub fn log_enabled(level: LogLevel) -> bool {
+    level as u8 <= LOG_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Writes `buf` as a hex dump to the print channel with the given index, 16 bytes per line with
+/// the offset of the first byte in front, e.g. `00000010: 48 65 6c 6c 6f`. The whole dump is
+/// written within one critical section so that it isn't interleaved with other output. Does
+/// nothing if the channel hasn't been set, the index is not less than [`MAX_PRINT_CHANNELS`] or
+/// the `disable-printing` feature is enabled. The error print channel can't be selected here.
+///
+/// See also [`rhexdump`].
+///
+/// [`rhexdump`]: crate::rhexdump
+pub fn hexdump(channel: usize, buf: &[u8]) {
+    if channel >= MAX_PRINT_CHANNELS {
+        return;
+    }
+
+    print_impl::with_writer(channel, 0, false, |w| {
+        for (line, chunk) in buf.chunks(16).enumerate() {
+            write!(w, "{:08x}:", line * 16).ok();
+            for byte in chunk {
+                write!(w, " {:02x}", byte).ok();
+            }
+            w.write_str("\n").ok();
         }
     });
 }
 
+/// Sets the mode of a channel until dropped, after which the previous mode is restored. Restoring
+/// in `Drop` ensures the channel can't be left in the overridden mode if the write is cut short.
+struct ModeOverride {
+    channel: *mut RttChannel,
+    previous: ChannelMode,
+}
+
+impl ModeOverride {
+    fn new(up: &UpChannel, mode: ChannelMode) -> Self {
+        let previous = up.mode();
+        up.channel().set_mode(mode);
+
+        ModeOverride {
+            channel: up.channel,
+            previous,
+        }
+    }
+}
+
+impl Drop for ModeOverride {
+    fn drop(&mut self) {
+        unsafe { (*self.channel).set_mode(self.previous) };
+    }
+}
+
 /// Public due to access from macro.
 #[doc(hidden)]
 pub mod print_impl {
     use super::*;
 
-    fn with_writer<F: Fn(TerminalWriter)>(number: u8, f: F) {
-        with_terminal_channel(|term| f(term.write(number)));
+    /// Public due to access from macro. Selects the error print channel in the printing functions.
+    #[doc(hidden)]
+    pub const ERROR_CHANNEL: usize = usize::MAX;
+
+    /// Public due to access from macro.
+    #[doc(hidden)]
+    pub const ENABLED: bool = !cfg!(feature = "disable-printing");
+
+    /// Public due to access from macro.
+    #[doc(hidden)]
+    pub fn with_writer<F: FnOnce(&mut TerminalWriter)>(
+        channel: usize,
+        number: u8,
+        line: bool,
+        f: F,
+    ) {
+        if !ENABLED {
+            return;
+        }
+
+        critical_section::with(|cs| with_writer_cs(cs, channel, number, line, None, f));
+    }
+
+    fn with_writer_cs<F: FnOnce(&mut TerminalWriter)>(
+        cs: CriticalSection,
+        channel: usize,
+        number: u8,
+        line: bool,
+        mode: Option<ChannelMode>,
+        f: F,
+    ) {
+        // Checked here so that every print path, including the blocking and `nocs` ones, is
+        // covered by the `disable-printing` feature
+        if !ENABLED {
+            return;
+        }
+
+        let terminal = match channel {
+            ERROR_CHANNEL => &PRINT_TERMINALS[ERROR_PRINT_CHANNEL],
+            _ => match PRINT_TERMINALS[..MAX_PRINT_CHANNELS].get(channel) {
+                Some(terminal) => terminal,
+                None => return,
+            },
+        };
+
+        if let Some(term) = &mut *terminal.borrow_ref_mut(cs) {
+            // Declared before the writer so that it is dropped, and the mode restored, only after
+            // the writer is done.
+            let _mode = mode.map(|mode| ModeOverride::new(&term.channel, mode));
+
+            let mut w = term.write(number);
+
+            if line {
+                if let Some(timestamp) = TIMESTAMP.borrow(cs).get() {
+                    write!(w, "[{}] ", timestamp()).ok();
+                }
+            }
+
+            f(&mut w);
+        }
+    }
+
+    /// Public due to access from macro.
+    #[doc(hidden)]
+    pub fn write_str(channel: usize, number: u8, s: &str) {
+        with_writer(channel, number, false, |w| {
+            w.write_str(s).ok();
+        });
+    }
+
+    /// Public due to access from macro.
+    #[doc(hidden)]
+    pub fn write_fmt(channel: usize, number: u8, arg: fmt::Arguments) {
+        with_writer(channel, number, false, |w| {
+            w.write_fmt(arg).ok();
+        });
+    }
+
+    /// Public due to access from macro.
+    #[doc(hidden)]
+    pub fn writeln_str(channel: usize, number: u8, s: &str) {
+        with_writer(channel, number, true, |w| {
+            w.write_str(s).ok();
+        });
+    }
+
+    /// Public due to access from macro.
+    #[doc(hidden)]
+    pub fn writeln_fmt(channel: usize, number: u8, arg: fmt::Arguments) {
+        with_writer(channel, number, true, |w| {
+            w.write_fmt(arg).ok();
+        });
+    }
+
+    /// Public due to access from macro.
+    #[doc(hidden)]
+    pub fn writeln_str_blocking(channel: usize, number: u8, s: &str) {
+        critical_section::with(|cs| {
+            with_writer_cs(
+                cs,
+                channel,
+                number,
+                true,
+                Some(ChannelMode::BlockIfFull),
+                |w| {
+                    w.write_str(s).ok();
+                },
+            )
+        });
+    }
+
+    /// Public due to access from macro.
+    #[doc(hidden)]
+    pub fn writeln_fmt_blocking(channel: usize, number: u8, arg: fmt::Arguments) {
+        critical_section::with(|cs| {
+            with_writer_cs(
+                cs,
+                channel,
+                number,
+                true,
+                Some(ChannelMode::BlockIfFull),
+                |w| {
+                    w.write_fmt(arg).ok();
+                },
+            )
+        });
     }
 
     /// Public due to access from macro.
+    ///
+    /// # Safety
+    ///
+    /// See [`rprint_nocs`](crate::rprint_nocs).
     #[doc(hidden)]
-    pub fn write_str(number: u8, s: &str) {
-        with_writer(number, |mut w| {
+    pub unsafe fn write_str_nocs(channel: usize, number: u8, s: &str) {
+        with_writer_cs(CriticalSection::new(), channel, number, false, None, |w| {
             w.write_str(s).ok();
         });
     }
 
     /// Public due to access from macro.
+    ///
+    /// # Safety
+    ///
+    /// See [`rprint_nocs`](crate::rprint_nocs).
     #[doc(hidden)]
-    pub fn write_fmt(number: u8, arg: fmt::Arguments) {
-        with_writer(number, |mut w| {
+    pub unsafe fn write_fmt_nocs(channel: usize, number: u8, arg: fmt::Arguments) {
+        with_writer_cs(CriticalSection::new(), channel, number, false, None, |w| {
+            w.write_fmt(arg).ok();
+        });
+    }
+
+    /// Public due to access from macro.
+    ///
+    /// # Safety
+    ///
+    /// See [`rprint_nocs`](crate::rprint_nocs).
+    #[doc(hidden)]
+    pub unsafe fn writeln_str_nocs(channel: usize, number: u8, s: &str) {
+        with_writer_cs(CriticalSection::new(), channel, number, true, None, |w| {
+            w.write_str(s).ok();
+        });
+    }
+
+    /// Public due to access from macro.
+    ///
+    /// # Safety
+    ///
+    /// See [`rprint_nocs`](crate::rprint_nocs).
+    #[doc(hidden)]
+    pub unsafe fn writeln_fmt_nocs(channel: usize, number: u8, arg: fmt::Arguments) {
+        with_writer_cs(CriticalSection::new(), channel, number, true, None, |w| {
             w.write_fmt(arg).ok();
         });
     }
 }
 
+/// rprint! implementation detail
+#[macro_export]
+#[doc(hidden)]
+macro_rules! rprint_impl {
+    ($write_str:path, $write_fmt:path; @ $channel:expr => $terminal:expr, $s:expr) => {
+        $write_str($channel, $terminal, $s);
+    };
+    ($write_str:path, $write_fmt:path; @ $channel:expr => $terminal:expr, $($arg:tt)*) => {
+        $write_fmt($channel, $terminal, format_args!($($arg)*));
+    };
+    ($write_str:path, $write_fmt:path; @ $channel:expr, $s:expr) => {
+        $write_str($channel, 0, $s);
+    };
+    ($write_str:path, $write_fmt:path; @ $channel:expr, $($arg:tt)*) => {
+        $write_fmt($channel, 0, format_args!($($arg)*));
+    };
+    ($write_str:path, $write_fmt:path; => $terminal:expr, $s:expr) => {
+        $write_str(0, $terminal, $s);
+    };
+    ($write_str:path, $write_fmt:path; => $terminal:expr, $($arg:tt)*) => {
+        $write_fmt(0, $terminal, format_args!($($arg)*));
+    };
+    ($write_str:path, $write_fmt:path; $s:expr) => {
+        $write_str(0, 0, $s);
+    };
+    ($write_str:path, $write_fmt:path; $($arg:tt)*) => {
+        $write_fmt(0, 0, format_args!($($arg)*));
+    };
+}
+
+/// rprintln! implementation detail
+#[macro_export]
+#[doc(hidden)]
+macro_rules! rprintln_impl {
+    ($write_str:path, $write_fmt:path; @ $channel:expr => $terminal:expr) => {
+        $write_str($channel, $terminal, "\n");
+    };
+    ($write_str:path, $write_fmt:path; @ $channel:expr => $terminal:expr, $fmt:expr) => {
+        $write_str($channel, $terminal, concat!($fmt, "\n"));
+    };
+    ($write_str:path, $write_fmt:path; @ $channel:expr => $terminal:expr, $fmt:expr, $($arg:tt)*) => {
+        $write_fmt($channel, $terminal, format_args!(concat!($fmt, "\n"), $($arg)*));
+    };
+    ($write_str:path, $write_fmt:path; @ $channel:expr) => {
+        $write_str($channel, 0, "\n");
+    };
+    ($write_str:path, $write_fmt:path; @ $channel:expr, $fmt:expr) => {
+        $write_str($channel, 0, concat!($fmt, "\n"));
+    };
+    ($write_str:path, $write_fmt:path; @ $channel:expr, $fmt:expr, $($arg:tt)*) => {
+        $write_fmt($channel, 0, format_args!(concat!($fmt, "\n"), $($arg)*));
+    };
+    ($write_str:path, $write_fmt:path; => $terminal:expr) => {
+        $write_str(0, $terminal, "\n");
+    };
+    ($write_str:path, $write_fmt:path; => $terminal:expr, $fmt:expr) => {
+        $write_str(0, $terminal, concat!($fmt, "\n"));
+    };
+    ($write_str:path, $write_fmt:path; => $terminal:expr, $fmt:expr, $($arg:tt)*) => {
+        $write_fmt(0, $terminal, format_args!(concat!($fmt, "\n"), $($arg)*));
+    };
+    ($write_str:path, $write_fmt:path;) => {
+        $write_str(0, 0, "\n");
+    };
+    ($write_str:path, $write_fmt:path; $fmt:expr) => {
+        $write_str(0, 0, concat!($fmt, "\n"));
+    };
+    ($write_str:path, $write_fmt:path; $fmt:expr, $($arg:tt)*) => {
+        $write_fmt(0, 0, format_args!(concat!($fmt, "\n"), $($arg)*));
+    };
+}
+
 /// Prints to the print RTT channel. Works just like the standard `print`.
 ///
 /// Before use the print channel has to be set with [`rtt_init_print`] or [`set_print_channel`]. If
@@ -62,20 +457,27 @@ pub mod print_impl {
 /// `rprint!(=> 1, "Hello!");` to write to terminal number 1, for example. Terminal numbers
 /// range from 0 to 15.
 ///
+/// To print to a print channel other than the default one, set with [`set_print_channel_at`],
+/// use the syntax `rprint!(@ 1, "Hello!");`, or `rprint!(@ 1 => 2, "Hello!");` to also select
+/// a virtual terminal. Indices from [`MAX_PRINT_CHANNELS`] up are out of range and the message is
+/// discarded; the error print channel can only be printed to with [`reprint`].
+///
+/// With the `disable-printing` feature enabled, this macro does nothing. The arguments are still
+/// type checked, but they are not evaluated, so any side effects they have don't happen.
+///
 /// [`rtt_init_print`]: crate::rtt_init_print
+/// [`MAX_PRINT_CHANNELS`]: crate::MAX_PRINT_CHANNELS
+/// [`reprint`]: crate::reprint
 #[macro_export]
 macro_rules! rprint {
-    (=> $terminal:expr, $s:expr) => {
-        $crate::print_impl::write_str($terminal, $s);
-    };
-    (=> $terminal:expr, $($arg:tt)*) => {
-        $crate::print_impl::write_fmt($terminal, format_args!($($arg)*));
-    };
-    ($s:expr) => {
-        $crate::print_impl::write_str(0, $s);
-    };
     ($($arg:tt)*) => {
-        $crate::print_impl::write_fmt(0, format_args!($($arg)*));
+        if $crate::print_impl::ENABLED {
+            $crate::rprint_impl!(
+                $crate::print_impl::write_str,
+                $crate::print_impl::write_fmt;
+                $($arg)*
+            );
+        }
     };
 }
 
@@ -88,26 +490,274 @@ macro_rules! rprint {
 /// `rprintln!(=> 1, "Hello!");` to write to terminal number 1, for example. Terminal numbers
 /// range from 0 to 15.
 ///
+/// To print to a print channel other than the default one, set with [`set_print_channel_at`],
+/// use the syntax `rprintln!(@ 1, "Hello!");`, or `rprintln!(@ 1 => 2, "Hello!");` to also
+/// select a virtual terminal. As with [`rprint`], the error print channel can't be selected by
+/// index; use [`reprintln`] for it.
+///
+/// With the `disable-printing` feature enabled, this macro does nothing. The arguments are still
+/// type checked, but they are not evaluated, so any side effects they have don't happen.
+///
 /// [`rtt_init_print`]: crate::rtt_init_print
+/// [`rprint`]: crate::rprint
+/// [`reprintln`]: crate::reprintln
 #[macro_export]
 macro_rules! rprintln {
+    ($($arg:tt)*) => {
+        if $crate::print_impl::ENABLED {
+            $crate::rprintln_impl!(
+                $crate::print_impl::writeln_str,
+                $crate::print_impl::writeln_fmt;
+                $($arg)*
+            );
+        }
+    };
+}
+
+/// Prints to the error print RTT channel. Works just like the standard `eprint`.
+///
+/// The channel has to be set with [`set_error_print_channel`] or [`rtt_init_print`] first. If the
+/// channel isn't set, the message is silently discarded. Virtual terminals can be selected with
+/// the same syntax as in [`rprint`], but selecting a print channel with `@` is not supported.
+///
+/// [`set_error_print_channel`]: crate::set_error_print_channel
+/// [`rtt_init_print`]: crate::rtt_init_print
+#[macro_export]
+macro_rules! reprint {
+    (@ $($_:tt)*) => {
+        compile_error!("reprint! always prints to the error print channel")
+    };
+    (=> $terminal:expr, $($arg:tt)*) => {
+        $crate::rprint!(@ $crate::print_impl::ERROR_CHANNEL => $terminal, $($arg)*)
+    };
+    ($($arg:tt)*) => {
+        $crate::rprint!(@ $crate::print_impl::ERROR_CHANNEL, $($arg)*)
+    };
+}
+
+/// Prints to the error print RTT channel, with a newline. Works just like the standard `eprintln`.
+///
+/// See [`reprint`] for details.
+///
+/// [`reprint`]: crate::reprint
+#[macro_export]
+macro_rules! reprintln {
+    (@ $($_:tt)*) => {
+        compile_error!("reprintln! always prints to the error print channel")
+    };
     (=> $terminal:expr) => {
-        $crate::print_impl::write_str($terminal, "\n");
+        $crate::rprintln!(@ $crate::print_impl::ERROR_CHANNEL => $terminal)
+    };
+    (=> $terminal:expr, $($arg:tt)*) => {
+        $crate::rprintln!(@ $crate::print_impl::ERROR_CHANNEL => $terminal, $($arg)*)
     };
-    (=> $terminal:expr, $fmt:expr) => {
-        $crate::print_impl::write_str($terminal, concat!($fmt, "\n"));
+    () => {
+        $crate::rprintln!(@ $crate::print_impl::ERROR_CHANNEL)
+    };
+    ($($arg:tt)*) => {
+        $crate::rprintln!(@ $crate::print_impl::ERROR_CHANNEL, $($arg)*)
+    };
+}
+
+/// Prints to the print RTT channel with a newline like [`rprintln`], but waits for buffer space as
+/// if the channel was in `BlockIfFull` mode instead of dropping the message.
+///
+/// The mode is only overridden within the critical section used for the write and is restored
+/// afterwards, so other writers to the channel keep using its normal mode. This is useful for
+/// single important messages on a channel that usually shouldn't block. The block timeout set with
+/// [`UpChannel::set_block_timeout`] still applies.
+///
+/// [`UpChannel::set_block_timeout`]: crate::UpChannel::set_block_timeout
+#[macro_export]
+macro_rules! rprintln_blocking {
+    ($($arg:tt)*) => {
+        $crate::rprintln_impl!(
+            $crate::print_impl::writeln_str_blocking,
+            $crate::print_impl::writeln_fmt_blocking;
+            $($arg)*
+        );
     };
-    (=> $terminal:expr, $fmt:expr, $($arg:tt)*) => {
-        $crate::print_impl::write_fmt($terminal, format_args!(concat!($fmt, "\n"), $($arg)*));
+}
+
+/// Prints to the print RTT channel with a newline like [`rprintln`], but only if the level is
+/// enabled with [`set_log_level`].
+///
+/// The level is checked before formatting, so a discarded message costs only an atomic load. The
+/// rest of the arguments are passed to [`rprintln`], including the extended syntax.
+///
+/// ```
+/// use rtt_target::{rlog, LogLevel};
+///
+/// rlog!(LogLevel::Debug, "sensor value: {}", value);
+/// rlog!(LogLevel::Warn, => 1, "low battery");
+/// ```
+///
+/// [`set_log_level`]: crate::set_log_level
+#[macro_export]
+macro_rules! rlog {
+    ($level:expr, $($arg:tt)*) => {
+        if $crate::log_enabled($level) {
+            $crate::rprintln!($($arg)*);
+        }
+    };
+}
+
+/// Writes a byte slice as a hex dump to the print RTT channel, see [`hexdump`] for the format.
+///
+/// Use the syntax `rhexdump!(@ 1, &buf)` to write to a print channel other than the default one.
+/// As with [`rprint`], the index must be less than [`MAX_PRINT_CHANNELS`], so the error print
+/// channel can't be selected.
+///
+/// ```
+/// rhexdump!(&packet);
+/// ```
+///
+/// [`hexdump`]: crate::hexdump
+/// [`rprint`]: crate::rprint
+/// [`MAX_PRINT_CHANNELS`]: crate::MAX_PRINT_CHANNELS
+#[macro_export]
+macro_rules! rhexdump {
+    (@ $channel:expr, $buf:expr) => {
+        $crate::hexdump($channel, $buf)
+    };
+    ($buf:expr) => {
+        $crate::hexdump(0, $buf)
+    };
+}
+
+/// Prints key/value pairs to the print RTT channel as a single line, in the form
+/// `key1=value1 key2=value2`. Keys and values are formatted with [`Display`], and the arguments
+/// must come in pairs; an odd number of arguments is a compile time error.
+///
+/// The line is written within one critical section, and gets the same timestamp prefix as lines
+/// printed with [`rprintln`]. This makes for a consistent log format that is easy to parse on the
+/// host.
+///
+/// ```
+/// rkv!("temp", 25, "state", "idle");
+/// ```
+///
+/// [`Display`]: core::fmt::Display
+#[macro_export]
+macro_rules! rkv {
+    ($key:expr, $value:expr $(, $rest_key:expr, $rest_value:expr)* $(,)?) => {
+        if $crate::print_impl::ENABLED {
+            $crate::print_impl::with_writer(0, 0, true, |w| {
+                use ::core::fmt::Write;
+
+                write!(w, "{}={}", $key, $value).ok();
+                $( write!(w, " {}={}", $rest_key, $rest_value).ok(); )*
+                w.write_str("\n").ok();
+            });
+        }
+    };
+    ($($arg:expr),* $(,)?) => {
+        compile_error!("rkv! takes an even number of arguments, as key/value pairs")
+    };
+}
+
+/// Prints to the print RTT channel using `ufmt` formatting. Otherwise this works exactly like
+/// [`rprint`], including the extended syntax, and can be used from any context.
+///
+/// `ufmt` generates considerably smaller code than `core::fmt`. The macro expands to
+/// `ufmt::uwrite!`, so the `ufmt` crate has to be a dependency of the calling crate.
+///
+/// ```
+/// urprint!("value: {}", 42);
+/// ```
+#[macro_export]
+macro_rules! urprint {
+    (@ $channel:expr => $terminal:expr, $($arg:tt)*) => {
+        $crate::print_impl::with_writer($channel, $terminal, false, |w| {
+            ::ufmt::uwrite!(w, $($arg)*).ok();
+        });
+    };
+    (@ $channel:expr, $($arg:tt)*) => {
+        $crate::urprint!(@ $channel => 0, $($arg)*);
+    };
+    (=> $terminal:expr, $($arg:tt)*) => {
+        $crate::urprint!(@ 0 => $terminal, $($arg)*);
+    };
+    ($($arg:tt)*) => {
+        $crate::urprint!(@ 0 => 0, $($arg)*);
+    };
+}
+
+/// Prints to the print RTT channel using `ufmt` formatting, with a newline. Otherwise this works
+/// exactly like [`rprintln`], including the extended syntax, and can be used from any context.
+///
+/// See [`urprint`] for details.
+#[macro_export]
+macro_rules! urprintln {
+    (@ $channel:expr => $terminal:expr) => {
+        $crate::urprintln!(@ $channel => $terminal, "");
+    };
+    (@ $channel:expr => $terminal:expr, $($arg:tt)*) => {
+        $crate::print_impl::with_writer($channel, $terminal, true, |w| {
+            ::ufmt::uwriteln!(w, $($arg)*).ok();
+        });
+    };
+    (@ $channel:expr) => {
+        $crate::urprintln!(@ $channel => 0, "");
+    };
+    (@ $channel:expr, $($arg:tt)*) => {
+        $crate::urprintln!(@ $channel => 0, $($arg)*);
+    };
+    (=> $terminal:expr) => {
+        $crate::urprintln!(@ 0 => $terminal, "");
+    };
+    (=> $terminal:expr, $($arg:tt)*) => {
+        $crate::urprintln!(@ 0 => $terminal, $($arg)*);
     };
     () => {
-        $crate::print_impl::write_str(0, "\n");
+        $crate::urprintln!(@ 0 => 0, "");
     };
-    ($fmt:expr) => {
-        $crate::print_impl::write_str(0, concat!($fmt, "\n"));
+    ($($arg:tt)*) => {
+        $crate::urprintln!(@ 0 => 0, $($arg)*);
     };
-    ($fmt:expr, $($arg:tt)*) => {
-        $crate::print_impl::write_fmt(0, format_args!(concat!($fmt, "\n"), $($arg)*));
+}
+
+/// Prints to the print RTT channel without taking a critical section. Otherwise this works exactly
+/// like [`rprint`], including the extended syntax.
+///
+/// Taking a critical section usually means disabling interrupts, which increases interrupt
+/// latency. If all printing happens from a single context, for example only from the main loop,
+/// the critical section is unnecessary overhead.
+///
+/// # Safety
+///
+/// The caller must guarantee that the print channel is never accessed concurrently, i.e. that no
+/// other printing macro or function accessing the print channel can preempt this one or run on
+/// another core at the same time. The macro must therefore be used within an `unsafe` block:
+///
+/// ```
+/// unsafe { rprint_nocs!("Hello from main!") };
+/// ```
+#[macro_export]
+macro_rules! rprint_nocs {
+    ($($arg:tt)*) => {
+        $crate::rprint_impl!(
+            $crate::print_impl::write_str_nocs,
+            $crate::print_impl::write_fmt_nocs;
+            $($arg)*
+        );
+    };
+}
+
+/// Prints to the print RTT channel with a newline, without taking a critical section. Otherwise
+/// this works exactly like [`rprintln`], including the extended syntax.
+///
+/// # Safety
+///
+/// The same requirements as for [`rprint_nocs`] apply.
+#[macro_export]
+macro_rules! rprintln_nocs {
+    ($($arg:tt)*) => {
+        $crate::rprintln_impl!(
+            $crate::print_impl::writeln_str_nocs,
+            $crate::print_impl::writeln_fmt_nocs;
+            $($arg)*
+        );
     };
 }
 
@@ -154,19 +804,55 @@ macro_rules! rdbg {
 /// Initializes RTT with a single up channel and sets it as the print channel for the printing
 /// macros.
 ///
-/// The optional arguments specify the blocking mode (default: `NoBlockSkip`) and size of the buffer
-/// in bytes (default: 1024). See [`rtt_init`] for more details.
+/// The optional arguments specify the blocking mode (default: `NoBlockSkip`), size of the buffer
+/// in bytes (default: 1024) and channel name (default: `"Terminal"`). See [`rtt_init`] for more
+/// details.
+///
+/// A distinct name makes it easier to tell apart the output of multiple targets in a host viewer:
 ///
+/// ```
+/// rtt_init_print!(NoBlockSkip, 1024, "AppLog");
+/// ```
+///
+/// A fourth argument adds up channel 1 named `"Error"` with the given size, and sets it as the
+/// error print channel for [`reprint`] and [`reprintln`]:
+///
+/// ```
+/// rtt_init_print!(NoBlockSkip, 1024, "Terminal", 256);
+/// ```
+///
+/// [`reprint`]: crate::reprint
+/// [`reprintln`]: crate::reprintln
 /// [`rtt_init`]: crate::rtt_init
 #[macro_export]
 macro_rules! rtt_init_print {
-    ($mode:path, $size:expr) => {{
+    ($mode:path, $size:expr, $name:literal, $error_size:expr) => {{
         let channels = $crate::rtt_init! {
             up: {
                 0: {
                     size: $size,
                     mode: $mode,
-                    name: "Terminal"
+                    name: $name
+                }
+                1: {
+                    size: $error_size,
+                    mode: $mode,
+                    name: "Error"
+                }
+            }
+        };
+
+        $crate::set_print_channel(channels.up.0);
+        $crate::set_error_print_channel(channels.up.1);
+    }};
+
+    ($mode:path, $size:expr, $name:literal) => {{
+        let channels = $crate::rtt_init! {
+            up: {
+                0: {
+                    size: $size,
+                    mode: $mode,
+                    name: $name
                 }
             }
         };
@@ -174,6 +860,10 @@ macro_rules! rtt_init_print {
         $crate::set_print_channel(channels.up.0);
     }};
 
+    ($mode:path, $size:expr) => {
+        $crate::rtt_init_print!($mode, $size, "Terminal");
+    };
+
     ($mode:path) => {
         $crate::rtt_init_print!($mode, 1024);
     };
@@ -183,3 +873,48 @@ macro_rules! rtt_init_print {
         $crate::rtt_init_print!(NoBlockSkip, 1024);
     }};
 }
+
+/// Initializes RTT with a print channel like [`rtt_init_print`], and a second up channel that is
+/// set as the panic channel with [`set_panic_channel`].
+///
+/// The optional arguments specify the blocking mode (default: `NoBlockSkip`) and size (default:
+/// 1024) of the print channel, and the size of the panic channel (default: 256). The panic channel
+/// is always in `BlockIfFull` mode, so panic messages are never dropped even if normal output is.
+///
+/// [`rtt_init_print`]: crate::rtt_init_print
+/// [`set_panic_channel`]: crate::set_panic_channel
+#[macro_export]
+macro_rules! rtt_init_print_panic {
+    ($mode:path, $size:expr, $panic_size:expr) => {{
+        let channels = $crate::rtt_init! {
+            up: {
+                0: {
+                    size: $size,
+                    mode: $mode,
+                    name: "Terminal"
+                }
+                1: {
+                    size: $panic_size,
+                    mode: $crate::ChannelMode::BlockIfFull,
+                    name: "Panic"
+                }
+            }
+        };
+
+        $crate::set_print_channel(channels.up.0);
+        $crate::set_panic_channel(channels.up.1);
+    }};
+
+    ($mode:path, $size:expr) => {
+        $crate::rtt_init_print_panic!($mode, $size, 256);
+    };
+
+    ($mode:path) => {
+        $crate::rtt_init_print_panic!($mode, 1024);
+    };
+
+    () => {{
+        use $crate::ChannelMode::NoBlockSkip;
+        $crate::rtt_init_print_panic!(NoBlockSkip, 1024);
+    }};
+}
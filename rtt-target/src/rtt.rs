@@ -3,17 +3,282 @@
 //! accessed from the rtt_init! macro.
 
 use crate::ChannelMode;
+#[cfg(any(feature = "stats", feature = "overflow-marker"))]
+use core::cell::Cell;
 use core::cmp::min;
+use core::convert::TryFrom;
+use core::ffi::CStr;
 use core::fmt;
+use core::ops::{Deref, DerefMut};
 use core::ptr;
-use portable_atomic::{AtomicUsize, Ordering::SeqCst};
+use core::slice;
+use portable_atomic::{AtomicPtr, Ordering::SeqCst};
+
+extern "C" {
+    #[link_name = "_SEGGER_RTT"]
+    static mut CONTROL_BLOCK: RttHeader;
+}
+
+/// Returns a pointer to the control block defined by the init macro.
+pub(crate) fn control_block() -> *mut RttHeader {
+    // Taking the address of a static is only safe on newer compilers
+    #[allow(unused_unsafe)]
+    unsafe {
+        ptr::addr_of_mut!(CONTROL_BLOCK)
+    }
+}
+
+/// Returns the address of the RTT control block, for example to tell host tools where to find it.
+///
+/// This is the address of the `_SEGGER_RTT` static defined by the init macro. Calling this function
+/// will cause a linking error if the init macro has not been called.
+pub fn control_block_addr() -> usize {
+    control_block() as usize
+}
+
+/// Returns the ID string stored at the start of the RTT control block, which host tools search for
+/// to find the control block. This is `"SEGGER RTT"` once the init macro has run, so something
+/// else, such as an empty string, means that the control block hasn't been initialized or has been
+/// overwritten. This can help track down why a debugger doesn't find the control block.
+///
+/// Like [`control_block_addr`], this will cause a linking error if the init macro has not been
+/// called.
+pub fn control_block_id() -> &'static str {
+    let id = unsafe { &(*control_block()).id };
+    let len = id.iter().position(|&b| b == 0).unwrap_or(id.len());
+
+    core::str::from_utf8(&id[..len]).unwrap_or("")
+}
+
+static BLOCK_WAIT_HOOK: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+/// Sets a function that is called between polls whenever the crate waits for the host, for example
+/// in `BlockIfFull` writes, [`UpChannel::flush`] and [`UpChannel::wait_for_space`]. Pass `None` to
+/// go back to the default, which is a tight spin loop.
+///
+/// This lets a low power design sleep between checks for buffer space instead of busy waiting, for
+/// example with `cortex_m::asm::wfe` or a short delay. The hook should return soon, because
+/// timeouts such as [`UpChannel::set_block_timeout`] are counted in polls, and it may be called
+/// within a critical section, so it must not wait for an interrupt to be handled.
+///
+/// [`UpChannel::flush`]: crate::UpChannel::flush
+/// [`UpChannel::wait_for_space`]: crate::UpChannel::wait_for_space
+/// [`UpChannel::set_block_timeout`]: crate::UpChannel::set_block_timeout
+pub fn set_block_wait_hook(hook: Option<fn()>) {
+    let hook = hook.map_or(ptr::null_mut(), |hook| hook as *mut ());
+    BLOCK_WAIT_HOOK.store(hook, SeqCst);
+}
+
+/// Waits between two polls of a channel, see [`set_block_wait_hook`].
+pub(crate) fn block_wait() {
+    let hook = BLOCK_WAIT_HOOK.load(SeqCst);
+
+    if hook.is_null() {
+        core::hint::spin_loop();
+    } else {
+        // The pointer was created from an `fn()` in set_block_wait_hook
+        let hook: fn() = unsafe { core::mem::transmute(hook) };
+        hook();
+    }
+}
+
+/// Control block ID stored backwards, so that the string itself doesn't appear in the binary where
+/// a host could mistake it for the control block.
+const MAGIC_STR_BACKWARDS: &[u8; 16] = b"\0\0\0\0\0\0TTR REGGES";
+
+/// Direction of an RTT channel.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Direction {
+    /// Target to host
+    Up,
+
+    /// Host to target
+    Down,
+}
+
+/// Description of a channel in the control block, returned by [`list_channels`].
+pub struct ChannelInfo {
+    /// Channel number within its direction.
+    pub number: usize,
+
+    /// Direction of the channel.
+    pub direction: Direction,
+
+    /// Name of the channel, or `None` if it has no name or the name is not valid UTF-8.
+    pub name: Option<&'static str>,
+
+    /// Size of the channel buffer in bytes.
+    pub size: usize,
+
+    /// Current blocking mode of the channel.
+    pub mode: ChannelMode,
+}
+
+/// Returns the up and down channel arrays of the control block. Both are empty if the init macro has
+/// not run yet.
+fn channels() -> (&'static [RttChannel], &'static [RttChannel]) {
+    let header = unsafe { &*control_block() };
+    if !header.is_initialized() {
+        return (&[], &[]);
+    }
+
+    unsafe {
+        let up = control_block().add(1).cast::<RttChannel>();
+        let down = up.add(header.max_up_channels());
+
+        (
+            slice::from_raw_parts(up, header.max_up_channels()),
+            slice::from_raw_parts(down, header.max_down_channels()),
+        )
+    }
+}
+
+/// Returns a pointer to the channel with the given direction and number in the control block, or
+/// `None` if the init macro has not run yet or the number is out of range.
+pub(crate) fn channel_ptr(direction: Direction, number: usize) -> Option<*mut RttChannel> {
+    let header = unsafe { &*control_block() };
+    let max = match direction {
+        Direction::Up => header.max_up_channels(),
+        Direction::Down => header.max_down_channels(),
+    };
+
+    if !header.is_initialized() || number >= max {
+        return None;
+    }
+
+    unsafe {
+        let up = control_block().add(1).cast::<RttChannel>();
+
+        Some(match direction {
+            Direction::Up => up.add(number),
+            Direction::Down => up.add(header.max_up_channels() + number),
+        })
+    }
+}
+
+/// Returns an iterator that describes every initialized channel in the control block, up channels
+/// first. The information is read from the control block, so it reflects the current mode of each
+/// channel even if the host has changed it.
+///
+/// The iterator is empty if the init macro has not run yet. Calling this function will cause a
+/// linking error if the init macro is never called.
+pub fn list_channels() -> impl Iterator<Item = ChannelInfo> {
+    let (up, down) = channels();
+
+    let up = up.iter().enumerate().map(|(n, c)| (Direction::Up, n, c));
+    let down = down
+        .iter()
+        .enumerate()
+        .map(|(n, c)| (Direction::Down, n, c));
+
+    up.chain(down)
+        .filter(|(_, _, channel)| channel.is_initialized())
+        .map(|(direction, number, channel)| ChannelInfo {
+            number,
+            direction,
+            name: channel.name(),
+            size: channel.size(),
+            mode: channel.mode(),
+        })
+}
+
+/// Sets the blocking mode of every initialized up channel in the control block, for example to
+/// switch to `BlockIfFull` once it is known that a debugger is attached.
+///
+/// Each channel is updated separately, so a write that is in progress in another context may
+/// finish partly in the old mode. The host can also change the modes at any time, so this is not a
+/// guarantee that all channels stay in the given mode.
+///
+/// Calling this function will cause a linking error if the init macro is never called.
+pub fn set_all_up_modes(mode: ChannelMode) {
+    for channel in channels().0.iter().filter(|c| c.is_initialized()) {
+        channel.set_mode(mode);
+    }
+}
+
+/// Types of the control block fields. They are `usize` and pointers by default, and 32 bits wide on
+/// any target with the `ptr32` feature, which is the layout that host tools expect from 32-bit
+/// chips.
+#[cfg(not(feature = "ptr32"))]
+mod field {
+    use portable_atomic::{AtomicUsize, Ordering};
+
+    pub type Word = usize;
+    pub type Ptr = *mut u8;
+
+    pub fn word(value: usize) -> Word {
+        value
+    }
+
+    pub fn from_word(value: Word) -> usize {
+        value
+    }
+
+    pub fn ptr(ptr: *mut u8) -> Ptr {
+        ptr
+    }
+
+    pub fn from_ptr(ptr: Ptr) -> *mut u8 {
+        ptr
+    }
+
+    #[repr(transparent)]
+    pub struct AtomicWord(AtomicUsize);
+
+    impl AtomicWord {
+        pub fn load(&self, order: Ordering) -> usize {
+            self.0.load(order)
+        }
+
+        pub fn store(&self, value: usize, order: Ordering) {
+            self.0.store(value, order)
+        }
+    }
+}
+
+#[cfg(feature = "ptr32")]
+mod field {
+    use portable_atomic::{AtomicU32, Ordering};
+
+    pub type Word = u32;
+    pub type Ptr = u32;
+
+    pub fn word(value: usize) -> Word {
+        value as u32
+    }
+
+    pub fn from_word(value: Word) -> usize {
+        value as usize
+    }
+
+    pub fn ptr(ptr: *mut u8) -> Ptr {
+        ptr as usize as u32
+    }
+
+    pub fn from_ptr(ptr: Ptr) -> *mut u8 {
+        ptr as usize as *mut u8
+    }
+
+    #[repr(transparent)]
+    pub struct AtomicWord(AtomicU32);
+
+    impl AtomicWord {
+        pub fn load(&self, order: Ordering) -> usize {
+            self.0.load(order) as usize
+        }
+
+        pub fn store(&self, value: usize, order: Ordering) {
+            self.0.store(value as u32, order)
+        }
+    }
+}
 
 // Note: this is zero-initialized in the initialization macro so all zeros must be a valid value
 #[repr(C)]
 pub struct RttHeader {
     id: [u8; 16],
-    max_up_channels: usize,
-    max_down_channels: usize,
+    max_up_channels: field::Word,
+    max_down_channels: field::Word,
     // Followed in memory by:
     // up_channels: [Channel; max_up_channels]
     // down_channels: [Channel; down_up_channels]
@@ -26,33 +291,44 @@ impl RttHeader {
     ///
     /// The arguments must correspond to the sizes of the arrays that follow the header in memory.
     pub unsafe fn init(&mut self, max_up_channels: usize, max_down_channels: usize) {
-        ptr::write_volatile(&mut self.max_up_channels, max_up_channels);
-        ptr::write_volatile(&mut self.max_down_channels, max_down_channels);
+        ptr::write_volatile(&mut self.max_up_channels, field::word(max_up_channels));
+        ptr::write_volatile(&mut self.max_down_channels, field::word(max_down_channels));
 
         // Copy the ID backward to avoid storing the magic string in the binary. The ID is
         // written backwards to make it less likely an unfinished control block is detected by the host.
 
-        const MAGIC_STR_BACKWARDS: &[u8; 16] = b"\0\0\0\0\0\0TTR REGGES";
-
-        for (idx, byte) in MAGIC_STR_BACKWARDS.into_iter().enumerate() {
+        for (idx, byte) in MAGIC_STR_BACKWARDS.iter().enumerate() {
             ptr::write_volatile(&mut self.id[15 - idx], *byte);
         }
     }
 
+    /// Returns true if the header has been fully initialized.
+    pub(crate) fn is_initialized(&self) -> bool {
+        // Compare backwards for the same reason the ID is written backwards
+        MAGIC_STR_BACKWARDS
+            .iter()
+            .enumerate()
+            .all(|(idx, byte)| unsafe { ptr::read_volatile(&self.id[15 - idx]) } == *byte)
+    }
+
     pub fn max_up_channels(&self) -> usize {
-        self.max_up_channels
+        field::from_word(self.max_up_channels)
+    }
+
+    pub(crate) fn max_down_channels(&self) -> usize {
+        field::from_word(self.max_down_channels)
     }
 }
 
 // Note: this is zero-initialized in the initialization macro so all zeros must be a valid value
 #[repr(C)]
 pub struct RttChannel {
-    name: *const u8,
-    buffer: *mut u8,
-    size: usize,
-    write: AtomicUsize,
-    read: AtomicUsize,
-    flags: AtomicUsize,
+    name: field::Ptr,
+    buffer: field::Ptr,
+    size: field::Word,
+    write: field::AtomicWord,
+    read: field::AtomicWord,
+    flags: field::AtomicWord,
 }
 
 impl RttChannel {
@@ -62,37 +338,161 @@ impl RttChannel {
     ///
     /// The pointer arguments must point to a valid null-terminated name and writable buffer.
     pub unsafe fn init(&mut self, name: *const u8, mode: ChannelMode, buffer: *mut [u8]) {
-        ptr::write_volatile(&mut self.name, name);
-        ptr::write_volatile(&mut self.size, (*buffer).len());
+        ptr::write_volatile(&mut self.name, field::ptr(name as *mut u8));
+        ptr::write_volatile(&mut self.size, field::word((&*buffer).len()));
         self.set_mode(mode);
 
         // Set buffer last as it can be used to detect if the channel has been initialized
-        ptr::write_volatile(&mut self.buffer, buffer as *mut u8);
+        ptr::write_volatile(&mut self.buffer, field::ptr(buffer as *mut u8));
+    }
+
+    /// Sets the name, mode and buffer of a channel at runtime, discarding any data in the old buffer
+    /// if there is one.
+    ///
+    /// # Safety
+    ///
+    /// The buffer must be writable, and nothing else may be accessing the channel on the target.
+    pub(crate) unsafe fn replace_buffer(
+        &mut self,
+        name: Option<&'static CStr>,
+        mode: ChannelMode,
+        buffer: *mut [u8],
+    ) {
+        // Hide the channel from the host while the size and pointers don't match
+        ptr::write_volatile(&mut self.buffer, field::ptr(ptr::null_mut()));
+
+        self.write.store(0, SeqCst);
+        self.read.store(0, SeqCst);
+
+        let name = name.map_or(ptr::null(), |name| name.as_ptr().cast());
+        self.init(name, mode, buffer);
+    }
+
+    /// Returns the channel name, or `None` if it's not set or not valid UTF-8.
+    pub(crate) fn name(&self) -> Option<&str> {
+        let name = field::from_ptr(unsafe { ptr::read_volatile(&self.name) });
+        if name.is_null() {
+            return None;
+        }
+
+        unsafe { CStr::from_ptr(name.cast()) }.to_str().ok()
     }
 
     /// Returns true on a non-null value of the (raw) buffer pointer
     pub fn is_initialized(&self) -> bool {
-        !self.buffer.is_null()
+        !self.buffer().is_null()
+    }
+
+    fn buffer(&self) -> *mut u8 {
+        field::from_ptr(self.buffer)
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        field::from_word(self.size)
     }
 
     pub(crate) fn mode(&self) -> ChannelMode {
         let mode = self.flags.load(SeqCst) & 3;
 
-        match mode {
-            0 => ChannelMode::NoBlockSkip,
-            1 => ChannelMode::NoBlockTrim,
-            2 => ChannelMode::BlockIfFull,
-            _ => ChannelMode::NoBlockSkip,
-        }
+        // The reserved value 3 can only come from the host, treat it like the default mode
+        ChannelMode::try_from(mode).unwrap_or(ChannelMode::NoBlockSkip)
+    }
+
+    pub(crate) fn is_blocking(&self) -> bool {
+        self.flags.load(SeqCst) & 3 == ChannelMode::BlockIfFull as usize
     }
 
     pub(crate) fn set_mode(&self, mode: ChannelMode) {
+        self.set_mode_bits(mode as usize);
+    }
+
+    fn set_mode_bits(&self, mode: usize) {
         self.flags
-            .store((self.flags.load(SeqCst) & !3) | mode as usize, SeqCst);
+            .store((self.flags.load(SeqCst) & !3) | mode, SeqCst);
+    }
+
+    pub(crate) fn flags(&self) -> usize {
+        self.flags.load(SeqCst)
+    }
+
+    /// Sets all flag bits except the mode bits, which keep their current value.
+    pub(crate) fn set_flags_raw(&self, flags: usize) {
+        self.flags
+            .store((flags & !3) | (self.flags.load(SeqCst) & 3), SeqCst);
+    }
+
+    // This method should only be called for down channels.
+    pub(crate) fn read(&self, buf: &mut [u8]) -> usize {
+        let (total, read) = self.copy_out(buf);
+
+        self.read.store(read, SeqCst);
+
+        total
+    }
+
+    // This method should only be called for down channels.
+    pub(crate) fn peek(&self, buf: &mut [u8]) -> usize {
+        self.copy_out(buf).0
+    }
+
+    // This method should only be called for down channels.
+    pub(crate) fn read_with<F: FnMut(&[u8])>(&self, mut f: F) -> usize {
+        let (write, mut read) = self.read_pointers();
+
+        let mut total = 0;
+
+        // Pass each contiguous part of the data to the callback (maximum of two iterations)
+        loop {
+            let count = self.readable_contiguous(write, read);
+            if count == 0 {
+                break;
+            }
+
+            f(unsafe { slice::from_raw_parts(self.buffer().add(read), count) });
+
+            total += count;
+            read += count;
+
+            if read >= self.size() {
+                // Wrap around to start
+                read = 0;
+            }
+        }
+
+        self.read.store(read, SeqCst);
+
+        total
     }
 
     // This method should only be called for down channels.
-    pub(crate) fn read(&self, mut buf: &mut [u8]) -> usize {
+    pub(crate) fn read_while<F: FnMut(u8) -> bool>(&self, mut f: F) -> usize {
+        let (write, mut read) = self.read_pointers();
+
+        let mut total = 0;
+
+        while read != write {
+            let byte = unsafe { ptr::read(self.buffer().add(read)) };
+            if !f(byte) {
+                break;
+            }
+
+            total += 1;
+            read += 1;
+
+            if read >= self.size() {
+                // Wrap around to start
+                read = 0;
+            }
+        }
+
+        self.read.store(read, SeqCst);
+
+        total
+    }
+
+    /// Copies data from the read pointer onwards into `buf` without consuming it. Returns the
+    /// number of bytes copied and the value the read pointer would have after consuming them.
+    fn copy_out(&self, mut buf: &mut [u8]) -> (usize, usize) {
         let (write, mut read) = self.read_pointers();
 
         let mut total = 0;
@@ -105,13 +505,13 @@ impl RttChannel {
             }
 
             unsafe {
-                ptr::copy_nonoverlapping(self.buffer.add(read), buf.as_mut_ptr(), count);
+                ptr::copy_nonoverlapping(self.buffer().add(read), buf.as_mut_ptr(), count);
             }
 
             total += count;
             read += count;
 
-            if read >= self.size {
+            if read >= self.size() {
                 // Wrap around to start
                 read = 0;
             }
@@ -119,25 +519,188 @@ impl RttChannel {
             buf = &mut buf[count..];
         }
 
-        self.read.store(read, SeqCst);
-
-        total
+        (total, read)
     }
 
     /// This method should only be called for up channels.
-    pub(crate) fn writer(&self) -> RttWriter<'_> {
+    pub(crate) fn writer<'c>(&'c self, stats: &'c Stats) -> RttWriter<'c> {
         RttWriter {
             chan: self,
+            stats,
             write: self.read_pointers().0,
             total: 0,
-            state: WriteState::Writable,
+            // A channel without a buffer can only be written to after a buffer is registered with
+            // UpChannelBuilder
+            state: if self.is_initialized() {
+                WriteState::Writable
+            } else {
+                WriteState::Finished
+            },
+            block_timeout: None,
+            muted: false,
+            skip_per_write: false,
+            #[cfg(feature = "overflow-marker")]
+            binary: false,
+        }
+    }
+
+    /// Writes a single byte directly, without the bookkeeping of an [`RttWriter`]. Returns false if
+    /// the byte was dropped. This method should only be called for up channels.
+    pub(crate) fn write_byte(&self, stats: &Stats, byte: u8, block_timeout: Option<u32>) -> bool {
+        #[cfg(feature = "overflow-marker")]
+        if stats.lost.get() {
+            // The marker has to be written before the byte, so take the slow path
+            let mut writer = self.writer(stats).block_timeout(block_timeout);
+            writer.write(&[byte]);
+            return writer.commit() == 1;
+        }
+
+        if !self.is_initialized() {
+            return false;
+        }
+
+        let mut spins = 0;
+
+        loop {
+            let (write, read) = self.read_pointers();
+
+            if self.writable_contiguous(write, read) > 0 {
+                unsafe {
+                    ptr::write(self.buffer().add(write), byte);
+                }
+
+                // Wrap around to start if needed
+                let write = if write + 1 >= self.size() {
+                    0
+                } else {
+                    write + 1
+                };
+                self.write.store(write, SeqCst);
+
+                stats.add_written(1);
+                stats.record_fill(self);
+                return true;
+            }
+
+            // A single byte can't be trimmed, so all non-blocking modes drop it
+            let timed_out = matches!(block_timeout, Some(max_spins) if spins >= max_spins);
+            if self.mode() != ChannelMode::BlockIfFull || timed_out {
+                stats.add_dropped(1);
+                return false;
+            }
+
+            spins += 1;
+            block_wait();
+        }
+    }
+
+    /// Copies `buf` to the buffer at the write pointer and commits it, without checking for free
+    /// space. This method should only be called for up channels.
+    ///
+    /// # Safety
+    ///
+    /// `buf` must not be longer than [`writable_len`](RttChannel::writable_len).
+    pub(crate) unsafe fn write_unchecked(&self, stats: &Stats, buf: &[u8]) {
+        let (write, _) = self.read_pointers();
+
+        // Copy up to the wrap point, and the rest to the start of the buffer
+        let first = min(buf.len(), self.size() - write);
+        ptr::copy_nonoverlapping(buf.as_ptr(), self.buffer().add(write), first);
+        ptr::copy_nonoverlapping(buf.as_ptr().add(first), self.buffer(), buf.len() - first);
+
+        let mut write = write + buf.len();
+        if write >= self.size() {
+            // Wrap around to start
+            write -= self.size();
+        }
+
+        self.write.store(write, SeqCst);
+        stats.add_written(buf.len());
+        stats.record_fill(self);
+    }
+
+    /// Discards all unread data by moving the read pointer to the write pointer.
+    pub(crate) fn clear(&self) {
+        let (write, _) = self.read_pointers();
+        self.read.store(write, SeqCst);
+    }
+
+    /// Reserves `len` bytes of contiguous space at the write pointer. This method should only be
+    /// called for up channels.
+    pub(crate) fn reserve<'c>(&'c self, stats: &'c Stats, len: usize) -> Option<WriteGuard<'c>> {
+        if !self.is_initialized() {
+            return None;
+        }
+
+        let (write, read) = self.read_pointers();
+
+        if len > self.writable_contiguous(write, read) {
+            return None;
+        }
+
+        Some(WriteGuard {
+            chan: self,
+            stats,
+            buf: unsafe { slice::from_raw_parts_mut(self.buffer().add(write), len) },
+            write,
+        })
+    }
+
+    /// Gets the total amount of space available for writing, including space after wrapping
+    /// around. One byte is always left free to distinguish a full buffer from an empty one.
+    pub(crate) fn writable_len(&self) -> usize {
+        let (write, read) = self.read_pointers();
+
+        if self.size() == 0 {
+            // A channel without a buffer has no space
+            0
+        } else if read > write {
+            read - write - 1
+        } else {
+            self.size() - write + read - 1
+        }
+    }
+
+    /// Gets the total amount of data available for reading, including data after wrapping around.
+    pub(crate) fn readable_len(&self) -> usize {
+        let (write, read) = self.read_pointers();
+
+        if read > write {
+            self.size() - read + write
+        } else {
+            write - read
+        }
+    }
+
+    /// Gets the amount of unread data as a percentage of the space that can hold data, which is one
+    /// byte less than the buffer size.
+    pub(crate) fn fill_percent(&self) -> u8 {
+        if self.size() < 2 {
+            return 0;
+        }
+
+        // readable_len handles the wrap around, so this is never more than 100
+        (self.readable_len() as u64 * 100 / (self.size() as u64 - 1)) as u8
+    }
+
+    /// Gets the amount of contiguous space available for writing
+    fn writable_contiguous(&self, write: usize, read: usize) -> usize {
+        if self.size() == 0 {
+            // A channel without a buffer has no space
+            0
+        } else if read > write {
+            read - write - 1
+        } else if read == 0 {
+            self.size() - write - 1
+        } else {
+            self.size() - write
         }
     }
 
     /// Gets the amount of contiguous data available for reading
     fn readable_contiguous(&self, write: usize, read: usize) -> usize {
         if read > write {
-            self.size - read
+            self.size() - read
         } else {
             write - read
         }
@@ -147,7 +710,7 @@ impl RttChannel {
         let write = self.write.load(SeqCst);
         let read = self.read.load(SeqCst);
 
-        if write >= self.size || read >= self.size {
+        if write >= self.size() || read >= self.size() {
             // Pointers have been corrupted. This doesn't happen in well-behaved programs, so
             // attempt to reset the buffer.
 
@@ -160,12 +723,108 @@ impl RttChannel {
     }
 }
 
+/// Sequence that is written to an up channel before the next write after data has been dropped,
+/// with the `overflow-marker` feature.
+#[cfg(feature = "overflow-marker")]
+pub const OVERFLOW_MARKER: &[u8] = b"\0<LOST>\0";
+
+/// Target side statistics for an up channel. These are kept in the channel object rather than the
+/// control block, because the control block layout is defined by the RTT protocol. Without the
+/// `stats` feature this is empty and updating it does nothing.
+#[derive(Default)]
+pub(crate) struct Stats {
+    #[cfg(feature = "stats")]
+    dropped: Cell<u64>,
+    #[cfg(feature = "stats")]
+    high_water_mark: Cell<usize>,
+    #[cfg(feature = "stats")]
+    written: Cell<u64>,
+    #[cfg(feature = "overflow-marker")]
+    lost: Cell<bool>,
+}
+
+impl Stats {
+    #[cfg_attr(
+        not(any(feature = "stats", feature = "overflow-marker")),
+        allow(unused_variables)
+    )]
+    pub(crate) fn add_dropped(&self, count: usize) {
+        #[cfg(feature = "stats")]
+        self.dropped
+            .set(self.dropped.get().saturating_add(count as u64));
+
+        #[cfg(feature = "overflow-marker")]
+        if count > 0 {
+            self.lost.set(true);
+        }
+    }
+
+    /// Returns the number of bytes that the overflow marker will take up in front of the next
+    /// write, or 0 if no marker is pending.
+    pub(crate) fn pending_marker_len(&self) -> usize {
+        #[cfg(feature = "overflow-marker")]
+        if self.lost.get() {
+            return OVERFLOW_MARKER.len();
+        }
+
+        0
+    }
+
+    #[cfg(feature = "stats")]
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.get()
+    }
+
+    #[cfg_attr(not(feature = "stats"), allow(unused_variables))]
+    fn add_written(&self, count: usize) {
+        #[cfg(feature = "stats")]
+        self.written
+            .set(self.written.get().saturating_add(count as u64));
+    }
+
+    #[cfg(feature = "stats")]
+    pub(crate) fn written(&self) -> u64 {
+        self.written.get()
+    }
+
+    #[cfg_attr(not(feature = "stats"), allow(unused_variables))]
+    fn record_fill(&self, chan: &RttChannel) {
+        #[cfg(feature = "stats")]
+        {
+            let fill = chan.readable_len();
+            if fill > self.high_water_mark.get() {
+                self.high_water_mark.set(fill);
+            }
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    pub(crate) fn high_water_mark(&self) -> usize {
+        self.high_water_mark.get()
+    }
+
+    #[cfg(feature = "stats")]
+    pub(crate) fn take(&self) -> crate::ChannelStats {
+        crate::ChannelStats {
+            dropped_bytes: self.dropped.replace(0),
+            total_written: self.written.replace(0),
+            high_water_mark: self.high_water_mark.replace(0),
+        }
+    }
+}
+
 /// A cancellable write operation to an RTT channel.
 pub(crate) struct RttWriter<'c> {
     chan: &'c RttChannel,
+    stats: &'c Stats,
     write: usize,
     total: usize,
     state: WriteState,
+    block_timeout: Option<u32>,
+    muted: bool,
+    skip_per_write: bool,
+    #[cfg(feature = "overflow-marker")]
+    binary: bool,
 }
 
 #[derive(Eq, PartialEq)]
@@ -181,11 +840,70 @@ enum WriteState {
 }
 
 impl RttWriter<'_> {
+    /// Limits the number of consecutive polls for free space in `BlockIfFull` mode.
+    pub fn block_timeout(mut self, max_spins: Option<u32>) -> Self {
+        self.block_timeout = max_spins;
+        self
+    }
+
+    /// Discards everything written without touching the buffer, if `muted` is true.
+    pub fn muted(mut self, muted: bool) -> Self {
+        if muted {
+            self.muted = true;
+            self.state = WriteState::Finished;
+        }
+        self
+    }
+
+    /// Skips each `write` call separately while the channel is in `NoBlockSkip` mode, if
+    /// `skip_per_write` is true. See [`UpChannel::set_skip_per_write`](crate::UpChannel).
+    pub fn skip_per_write(mut self, skip_per_write: bool) -> Self {
+        self.skip_per_write = skip_per_write;
+        self
+    }
+
+    /// Leaves out [`OVERFLOW_MARKER`] for binary data that it would corrupt. A pending marker is
+    /// then written before the next write that isn't binary instead.
+    #[cfg_attr(not(feature = "overflow-marker"), allow(unused_mut))]
+    pub fn binary(mut self) -> Self {
+        #[cfg(feature = "overflow-marker")]
+        {
+            self.binary = true;
+        }
+        self
+    }
+
     pub fn write(&mut self, buf: &[u8]) {
-        self.write_with_mode(self.chan.mode(), buf);
+        let mode = self.chan.mode();
+        let per_write = self.skip_per_write && mode == ChannelMode::NoBlockSkip;
+
+        self.write_impl(mode, per_write, buf);
+    }
+
+    pub fn write_with_mode(&mut self, mode: ChannelMode, buf: &[u8]) {
+        self.write_impl(mode, false, buf);
     }
 
-    pub fn write_with_mode(&mut self, mode: ChannelMode, mut buf: &[u8]) {
+    /// With `per_write` set, a `NoBlockSkip` write only skips `buf` and anything after it, instead
+    /// of the whole operation.
+    fn write_impl(&mut self, mode: ChannelMode, per_write: bool, mut buf: &[u8]) {
+        if self.muted {
+            return;
+        }
+
+        let mut spins = 0;
+
+        #[cfg(feature = "overflow-marker")]
+        if !self.binary && self.total == 0 && !buf.is_empty() && self.state == WriteState::Writable
+        {
+            self.write_overflow_marker(mode, per_write);
+        }
+
+        if per_write && self.state == WriteState::Writable && buf.len() > self.writable_total() {
+            // Drop this part and anything after it, but keep what has been written so far
+            self.state = WriteState::Full;
+        }
+
         while self.state == WriteState::Writable && !buf.is_empty() {
             let count = min(self.writable_contiguous(), buf.len());
 
@@ -193,56 +911,123 @@ impl RttWriter<'_> {
                 // Buffer is full
 
                 match mode {
-                    ChannelMode::NoBlockSkip => {
+                    ChannelMode::NoBlockSkip if !per_write => {
                         // Mark the entire operation as failed if even one part cannot be written in
                         // full.
                         self.state = WriteState::Finished;
+                        // The overflow marker isn't part of total, so only data counts as dropped
+                        self.stats.add_dropped(self.total + buf.len());
+                        // Nothing is committed, so nothing counts as written
+                        self.total = 0;
                         return;
                     }
 
-                    ChannelMode::NoBlockTrim => {
+                    ChannelMode::NoBlockSkip | ChannelMode::NoBlockTrim => {
                         // If the buffer is full, write as much as possible (note: no return), and
                         // mark the operation as full, which prevents further writes.
                         self.state = WriteState::Full;
                     }
 
                     ChannelMode::BlockIfFull => {
-                        // Commit everything written so far and spin until more can be written
+                        // Commit everything written so far and spin until more can be written.
+                        // Only some free space is waited for, not room for all of buf, so that data
+                        // larger than the whole buffer doesn't wait forever.
                         self.chan.write.store(self.write, SeqCst);
+                        self.stats.record_fill(self.chan);
+
+                        if let Some(max_spins) = self.block_timeout {
+                            if spins >= max_spins {
+                                // The host isn't reading, give up and drop the rest like
+                                // NoBlockTrim.
+                                self.state = WriteState::Full;
+                            }
+                            spins += 1;
+                        }
+
+                        if self.state == WriteState::Writable {
+                            block_wait();
+                        }
+
                         continue;
                     }
                 }
+            } else {
+                spins = 0;
             }
 
             unsafe {
-                ptr::copy_nonoverlapping(buf.as_ptr(), self.chan.buffer.add(self.write), count);
+                ptr::copy_nonoverlapping(buf.as_ptr(), self.chan.buffer().add(self.write), count);
             }
 
             self.write += count;
             self.total += count;
 
-            if self.write >= self.chan.size {
+            if self.write >= self.chan.size() {
                 // Wrap around to start
                 self.write = 0;
             }
 
             buf = &buf[count..];
         }
+
+        // Anything left over was trimmed, or the operation had already failed or filled up
+        self.stats.add_dropped(buf.len());
     }
 
     /// Gets the amount of contiguous space available for writing
     fn writable_contiguous(&self) -> usize {
         let read = self.chan.read_pointers().1;
 
-        if read > self.write {
+        self.chan.writable_contiguous(self.write, read)
+    }
+
+    /// Writes [`OVERFLOW_MARKER`] if data has been dropped since the last successful write. This is
+    /// called at the start of an operation, so the marker is committed together with the data that
+    /// follows.
+    #[cfg(feature = "overflow-marker")]
+    fn write_overflow_marker(&mut self, mode: ChannelMode, per_write: bool) {
+        if !self.stats.lost.replace(false) {
+            return;
+        }
+
+        if mode != ChannelMode::BlockIfFull && self.writable_total() < OVERFLOW_MARKER.len() {
+            // The marker has to come right before the next data that is written, so nothing can be
+            // written until there is room for it. The data that is dropped instead sets the flag
+            // again.
+            self.state = if mode == ChannelMode::NoBlockSkip && !per_write {
+                WriteState::Finished
+            } else {
+                WriteState::Full
+            };
+            return;
+        }
+
+        self.write_with_mode(mode, OVERFLOW_MARKER);
+
+        // The marker doesn't count as written data. It may have been cut short by a block timeout.
+        self.total = self.total.saturating_sub(OVERFLOW_MARKER.len());
+    }
+
+    /// Gets the total amount of space available for writing, including after the wrap point
+    fn writable_total(&self) -> usize {
+        let read = self.chan.read_pointers().1;
+
+        if self.chan.size() == 0 {
+            // A channel without a buffer has no space
+            0
+        } else if read > self.write {
             read - self.write - 1
-        } else if read == 0 {
-            self.chan.size - self.write - 1
         } else {
-            self.chan.size - self.write
+            self.chan.size() - self.write + read - 1
         }
     }
 
+    /// Returns true if the write operation was abandoned because the data didn't fit in
+    /// `NoBlockSkip` mode. Only meaningful before the writer is committed.
+    pub fn is_skipped(&self) -> bool {
+        self.state == WriteState::Finished
+    }
+
     pub fn is_failed(&self) -> bool {
         self.state != WriteState::Finished
     }
@@ -259,6 +1044,8 @@ impl RttWriter<'_> {
             WriteState::Full | WriteState::Writable => {
                 // Commit the write pointer so the host can see the new data
                 self.chan.write.store(self.write, SeqCst);
+                self.stats.add_written(self.total);
+                self.stats.record_fill(self.chan);
                 self.state = WriteState::Finished;
             }
         }
@@ -271,9 +1058,167 @@ impl Drop for RttWriter<'_> {
     }
 }
 
+/// Space reserved in an up channel buffer with [`UpChannel::reserve`](crate::UpChannel::reserve).
+///
+/// The guard dereferences to the reserved part of the ring buffer. The data becomes visible to the
+/// host when the guard is dropped.
+pub struct WriteGuard<'c> {
+    chan: &'c RttChannel,
+    stats: &'c Stats,
+    buf: &'c mut [u8],
+    write: usize,
+}
+
+impl WriteGuard<'_> {
+    /// Shortens the reservation to `len` bytes, so that only the first `len` bytes are written when
+    /// the guard is dropped. Has no effect if `len` is not smaller than the current length.
+    pub fn truncate(&mut self, len: usize) {
+        let buf = core::mem::take(&mut self.buf);
+        let len = min(len, buf.len());
+        self.buf = &mut buf[..len];
+    }
+}
+
+impl Deref for WriteGuard<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.buf
+    }
+}
+
+impl DerefMut for WriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.buf
+    }
+}
+
+impl Drop for WriteGuard<'_> {
+    fn drop(&mut self) {
+        let mut write = self.write + self.buf.len();
+
+        if write >= self.chan.size() {
+            // Wrap around to start
+            write = 0;
+        }
+
+        self.chan.write.store(write, SeqCst);
+        self.stats.add_written(self.buf.len());
+        self.stats.record_fill(self.chan);
+    }
+}
+
 impl fmt::Write for RttWriter<'_> {
     fn write_str(&mut self, s: &str) -> Result<(), fmt::Error> {
         self.write(s.as_bytes());
         Ok(())
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::boxed::Box;
+    use std::vec;
+    use std::vec::Vec;
+
+    /// Creates a channel with a buffer of `size` bytes. Both are leaked so that they can be shared
+    /// with a simulated host for the rest of the test.
+    pub(crate) fn channel(size: usize, mode: ChannelMode) -> &'static mut RttChannel {
+        let chan: &mut RttChannel = Box::leak(Box::new(unsafe { core::mem::zeroed() }));
+        let buf: &mut [u8] = Box::leak(vec![0u8; size].into_boxed_slice());
+
+        unsafe { chan.init(ptr::null(), mode, buf) };
+
+        chan
+    }
+
+    /// Reads all data that is in the buffer, like the host does for an up channel.
+    pub(crate) fn read_all(chan: &RttChannel) -> Vec<u8> {
+        let mut data = Vec::new();
+        chan.read_with(|part| data.extend_from_slice(part));
+
+        data
+    }
+
+    /// Lets a simulated host thread access a channel, like a debug probe does.
+    struct Host(&'static RttChannel);
+
+    unsafe impl Send for Host {}
+
+    #[test]
+    fn blocking_write_larger_than_buffer_arrives_in_order() {
+        let chan: &RttChannel = channel(1024, ChannelMode::BlockIfFull);
+        let data: Vec<u8> = (0..4096).map(|i| (i % 251) as u8).collect();
+        let done = core::sync::atomic::AtomicBool::new(false);
+
+        let received = std::thread::scope(|s| {
+            let host = Host(chan);
+            let done = &done;
+            let reader = s.spawn(move || {
+                // Move the whole wrapper, not just the reference in it which isn't Send
+                let host = host;
+                let mut received = Vec::new();
+                let mut buf = [0u8; 100];
+
+                loop {
+                    // Check the flag before reading so that nothing written before it is missed
+                    let finished = done.load(SeqCst);
+                    let count = host.0.read(&mut buf);
+                    received.extend_from_slice(&buf[..count]);
+
+                    if finished && count == 0 {
+                        break received;
+                    }
+                }
+            });
+
+            let stats = Stats::default();
+            let mut writer = chan.writer(&stats);
+            writer.write(&data);
+            assert_eq!(writer.commit(), data.len());
+            done.store(true, SeqCst);
+
+            reader.join().unwrap()
+        });
+
+        assert_eq!(received, data);
+    }
+
+    #[test]
+    fn read_exact_wraps_around_end_of_buffer() {
+        let chan = channel(16, ChannelMode::NoBlockSkip);
+        chan.read.store(12, SeqCst);
+        chan.write.store(12, SeqCst);
+
+        // Written by the host, 4 bytes before and 4 bytes after the wrap point
+        let stats = Stats::default();
+        let mut writer = chan.writer(&stats);
+        writer.write(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        writer.commit();
+        assert_eq!(chan.write.load(SeqCst), 4);
+
+        let mut down = unsafe { crate::DownChannel::new(chan, 0) };
+        let mut buf = [0u8; 8];
+        assert_eq!(down.read_exact(&mut buf, 0), Ok(()));
+        assert_eq!(buf, [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(chan.read.load(SeqCst), 4);
+    }
+
+    #[test]
+    fn read_exact_returns_partial_count() {
+        let chan = channel(16, ChannelMode::NoBlockSkip);
+        chan.read.store(14, SeqCst);
+        chan.write.store(14, SeqCst);
+
+        let stats = Stats::default();
+        let mut writer = chan.writer(&stats);
+        writer.write(&[1, 2, 3]);
+        writer.commit();
+
+        let mut down = unsafe { crate::DownChannel::new(chan, 0) };
+        let mut buf = [0u8; 8];
+        assert_eq!(down.read_exact(&mut buf, 10), Err(3));
+        assert_eq!(buf[..3], [1, 2, 3]);
+    }
+}
@@ -0,0 +1,29 @@
+//! Runs `rtt_init` on the host and checks the modes it writes into the control block. This is its
+//! own test binary because `rtt_init` can only be called once per program.
+
+use rtt_target::{rtt_init, ChannelMode};
+
+#[test]
+fn mode_is_set_before_channels_are_returned() {
+    let channels = rtt_init! {
+        up: {
+            0: { size: 64, mode: ChannelMode::BlockIfFull, name: "Terminal" }
+            1: { size: 32, mode: ChannelMode::NoBlockTrim }
+            2: { size: 32 }
+            3: { size: 32, skip_per_write: true, name: "Log" }
+        }
+        down: {
+            0: { size: 16, mode: ChannelMode::BlockIfFull }
+        }
+    };
+
+    assert_eq!(channels.up.0.mode(), ChannelMode::BlockIfFull);
+    assert_eq!(channels.up.1.mode(), ChannelMode::NoBlockTrim);
+    assert_eq!(channels.up.2.mode(), ChannelMode::NoBlockSkip);
+    assert!(!channels.up.2.skip_per_write());
+    assert!(channels.up.3.skip_per_write());
+    assert_eq!(
+        channels.up.0.flags_raw() & 3,
+        ChannelMode::BlockIfFull as usize
+    );
+}
@@ -0,0 +1,40 @@
+//! Declares 8 up and 4 down channels with `rtt_init` and checks that the highest ones are wired to
+//! the right control block entries.
+
+use rtt_target::{rtt_init, ChannelMode};
+
+#[test]
+fn highest_channels_are_usable() {
+    let mut channels = rtt_init! {
+        up: {
+            0: { size: 16 }
+            1: { size: 16 }
+            2: { size: 16 }
+            3: { size: 16 }
+            4: { size: 16 }
+            5: { size: 16 }
+            6: { size: 16 }
+            7: { size: 64, mode: ChannelMode::NoBlockTrim, name: "Last" }
+        }
+        down: {
+            0: { size: 16 }
+            1: { size: 16 }
+            2: { size: 16 }
+            3: { size: 32, name: "LastDown" }
+        }
+    };
+
+    assert_eq!(channels.up.7.number(), 7);
+    assert_eq!(channels.up.7.name(), Some("Last"));
+    assert_eq!(channels.up.7.buffer_len(), 64);
+    assert_eq!(channels.up.7.mode(), ChannelMode::NoBlockTrim);
+
+    assert_eq!(channels.up.7.write(b"to the last channel"), 19);
+    assert_eq!(channels.up.7.writable_len(), 64 - 1 - 19);
+    assert_eq!(channels.up.6.writable_len(), 16 - 1);
+
+    assert_eq!(channels.down.3.number(), 3);
+    assert_eq!(channels.down.3.name(), Some("LastDown"));
+    assert_eq!(channels.down.3.buffer_len(), 32);
+    assert_eq!(channels.down.3.available(), 0);
+}